@@ -150,8 +150,8 @@ impl CodeGenerator {
                         CompareOp::Eq => a == b,
                     };
                     writeln!(self.stream, "\tmovq ${}, %{}", res as u8, dst)
-                } else if let Value::Number(n) = lhs {
-                    writeln!(self.stream, "\tcmpq ${}, {}", n, self.format_value(rhs))?;
+                } else if let Value::Number(_) = lhs {
+                    self.emit_compare(lhs, rhs)?;
                     let cmp = match cmp {
                         CompareOp::Lt => "setg",
                         CompareOp::Le => "setge",
@@ -161,12 +161,7 @@ impl CodeGenerator {
                     writeln!(self.stream, "\t{} {}", cmp, dst_8_bit)?;
                     writeln!(self.stream, "\tmovzbq {}, %{}", dst_8_bit, dst)
                 } else {
-                    writeln!(
-                        self.stream,
-                        "\tcmpq {}, {}",
-                        self.format_value(rhs),
-                        self.format_value(lhs)
-                    )?;
+                    self.emit_compare(rhs, lhs)?;
                     let cmp = match cmp {
                         CompareOp::Lt => "setl",
                         CompareOp::Le => "setle",
@@ -194,8 +189,8 @@ impl CodeGenerator {
                     } else {
                         Ok(())
                     }
-                } else if let Value::Number(n) = lhs {
-                    writeln!(self.stream, "\tcmpq ${}, {}", n, self.format_value(rhs))?;
+                } else if let Value::Number(_) = lhs {
+                    self.emit_compare(lhs, rhs)?;
                     let jmp = match cmp {
                         CompareOp::Lt => "jg",
                         CompareOp::Le => "jge",
@@ -203,12 +198,7 @@ impl CodeGenerator {
                     };
                     writeln!(self.stream, "\t{} _{}", jmp, label)
                 } else {
-                    writeln!(
-                        self.stream,
-                        "\tcmpq {}, {}",
-                        self.format_value(rhs),
-                        self.format_value(lhs)
-                    )?;
+                    self.emit_compare(rhs, lhs)?;
                     let jmp = match cmp {
                         CompareOp::Lt => "jl",
                         CompareOp::Le => "jle",
@@ -228,12 +218,45 @@ impl CodeGenerator {
             }
             Call { callee, args } => {
                 writeln!(self.stream, "\tsubq ${}, %rsp", (args - 6).max(0) * 8 + 8)?;
-                let name = match callee {
-                    Value::Register(reg) => format!("*%{}", reg),
-                    Value::Function(label) => format!("_{}", label),
-                    _ => panic!("call invalid callee"),
+                writeln!(self.stream, "\tjmp {}", self.format_callee(callee))
+            }
+            TailCall {
+                callee,
+                args: num_args,
+            } => {
+                // the callee's stack arguments replace our own below the return
+                // address, so they must fit in the space the caller gave us
+                let stack_args = (num_args - 6).max(0);
+                let frame_args = (args - 6).max(0);
+                if stack_args > frame_args {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "tail call passes {} stack arguments but the frame only has {}",
+                            stack_args, frame_args
+                        ),
+                    ));
+                }
+
+                let scratch = match callee {
+                    Value::Register(Register::RAX) => Register::R11,
+                    _ => Register::RAX,
                 };
-                writeln!(self.stream, "\tjmp {}", name)
+                for i in 0..stack_args {
+                    writeln!(self.stream, "\tmovq {}(%rsp), %{}", -16 - 8 * i, scratch)?;
+                    writeln!(
+                        self.stream,
+                        "\tmovq %{}, {}(%rsp)",
+                        scratch,
+                        (locals + frame_args - 1 - i) * 8
+                    )?;
+                }
+
+                let stack_size = (locals + frame_args - stack_args) * 8;
+                if stack_size > 0 {
+                    writeln!(self.stream, "\taddq ${}, %rsp", stack_size)?;
+                }
+                writeln!(self.stream, "\tjmp {}", self.format_callee(callee))
             }
             Print => writeln!(self.stream, "\tcall print"),
             Allocate => writeln!(self.stream, "\tcall allocate"),
@@ -265,6 +288,23 @@ impl CodeGenerator {
         }
     }
 
+    // cmpq only encodes sign-extended 32-bit immediates, so wider ones go
+    // through a scratch register that is saved around the compare; popq
+    // leaves the flags alone
+    fn emit_compare(&mut self, src: &Value, dst: &Value) -> io::Result<()> {
+        let dst = self.format_value(dst);
+        match src {
+            Value::Number(n) if i32::try_from(*n).is_err() => {
+                let scratch = if dst == "%r11" { "%rax" } else { "%r11" };
+                writeln!(self.stream, "\tpushq {}", scratch)?;
+                writeln!(self.stream, "\tmovq ${}, {}", n, scratch)?;
+                writeln!(self.stream, "\tcmpq {}, {}", scratch, dst)?;
+                writeln!(self.stream, "\tpopq {}", scratch)
+            }
+            _ => writeln!(self.stream, "\tcmpq {}, {}", self.format_value(src), dst),
+        }
+    }
+
     fn format_value(&self, val: &Value) -> String {
         match val {
             Value::Register(r) => format!("%{}", r),
@@ -274,6 +314,14 @@ impl CodeGenerator {
         }
     }
 
+    fn format_callee(&self, callee: &Value) -> String {
+        match callee {
+            Value::Register(reg) => format!("*%{}", reg),
+            Value::Function(label) => format!("_{}", label),
+            _ => panic!("call invalid callee"),
+        }
+    }
+
     fn format_value_8_bit(&self, val: &Value) -> String {
         match val {
             Value::Register(r) => self.format_register_8_bit(r).into(),
@@ -176,6 +176,10 @@ pub enum Instruction {
         callee: Value,
         args: i64,
     },
+    TailCall {
+        callee: Value,
+        args: i64,
+    },
     Print,
     Input,
     Allocate,
@@ -230,6 +234,7 @@ impl fmt::Display for Instruction {
             Goto(label) => write!(f, "goto :{}", label),
             Return => write!(f, "return"),
             Call { callee, args } => write!(f, "call {} {}", callee, args),
+            TailCall { callee, args } => write!(f, "tail-call {} {}", callee, args),
             Print => write!(f, "call print 1"),
             Input => write!(f, "call input 0"),
             Allocate => write!(f, "call allocate 2"),
@@ -262,6 +262,15 @@ fn instruction<'src>() -> impl Parser<'src, &'src str, Instruction, MyExtra<'src
         .then(number())
         .map(|(callee, args)| Instruction::Call { callee, args });
 
+    // tail-call u N: passes N arguments like `call u N` but jumps to u in
+    // place of returning, reusing the frame of the current function. Its
+    // stack arguments must fit in the ones the current function received.
+    let tail_call = just("tail-call")
+        .padded_by(separators())
+        .ignore_then(write_or_function())
+        .then(number())
+        .map(|(callee, args)| Instruction::TailCall { callee, args });
+
     let print = call_keyword
         .then(just("print").padded_by(separators()))
         .then(just('1').padded_by(separators()))
@@ -331,6 +340,7 @@ fn instruction<'src>() -> impl Parser<'src, &'src str, Instruction, MyExtra<'src
         goto,
         return_inst,
         call_inst,
+        tail_call,
         print,
         input,
         allocate,
@@ -4,4 +4,4 @@ mod loops;
 
 pub use crate::analysis::dominators::compute_dominators;
 pub use crate::analysis::liveness::{LivenessResult, compute_liveness};
-pub use crate::analysis::loops::{LoopForest, compute_loops};
+pub use crate::analysis::loops::compute_loops;
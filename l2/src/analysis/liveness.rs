@@ -1,5 +1,5 @@
 use l2::*;
-use utils::{BitVector, DisplayResolved, Interner, Worklist};
+use utils::{BitVector, Dataflow, Direction, DisplayResolved, Interner, solve};
 
 #[derive(Debug)]
 pub struct LivenessResult {
@@ -45,55 +45,74 @@ impl DisplayResolved for LivenessResult {
     }
 }
 
-pub fn compute_liveness(func: &Function) -> LivenessResult {
-    let interner = func
-        .basic_blocks
-        .iter()
-        .flat_map(|block| &block.instructions)
-        .flat_map(|inst| inst.uses().into_iter().chain(inst.defs()))
-        .chain(Register::gp_registers().into_iter().map(Value::Register))
-        .fold(Interner::new(), |mut interner, val| {
-            interner.intern(val);
-            interner
-        });
-
-    let num_gp_variables = interner.len();
-    let num_blocks = func.basic_blocks.len();
-    let mut block_gen = vec![BitVector::new(num_gp_variables); num_blocks];
-    let mut block_kill = vec![BitVector::new(num_gp_variables); num_blocks];
+#[derive(Debug)]
+struct LivenessAnalysis {
+    interner: Interner<Value>,
+    block_gen: Vec<BitVector>,
+    block_kill: Vec<BitVector>,
+}
 
-    for (i, block) in func.basic_blocks.iter().enumerate() {
-        for inst in &block.instructions {
-            block_gen[i].set_from(inst.uses().iter().filter_map(|use_| {
-                let j = interner[use_];
-                (!block_kill[i].test(j)).then_some(j)
-            }));
-            block_kill[i].set_from(inst.defs().iter().map(|def| interner[def]));
+impl LivenessAnalysis {
+    pub fn new(func: &Function) -> Self {
+        let interner = func
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .flat_map(|inst| inst.uses().into_iter().chain(inst.defs()))
+            .chain(Register::gp_registers().into_iter().map(Value::Register))
+            .fold(Interner::new(), |mut interner, val| {
+                interner.intern(val);
+                interner
+            });
+
+        let num_gp_variables = interner.len();
+        let num_blocks = func.basic_blocks.len();
+        let mut block_gen = vec![BitVector::new(num_gp_variables); num_blocks];
+        let mut block_kill = vec![BitVector::new(num_gp_variables); num_blocks];
+
+        for (i, block) in func.basic_blocks.iter().enumerate() {
+            for inst in &block.instructions {
+                block_gen[i].set_from(inst.uses().iter().filter_map(|use_| {
+                    let j = interner[use_];
+                    (!block_kill[i].test(j)).then_some(j)
+                }));
+                block_kill[i].set_from(inst.defs().iter().map(|def| interner[def]));
+            }
+        }
+
+        LivenessAnalysis {
+            interner,
+            block_gen,
+            block_kill,
         }
     }
+}
 
-    let mut block_in = vec![BitVector::new(num_gp_variables); num_blocks];
-    let mut block_out = vec![BitVector::new(num_gp_variables); num_blocks];
-    let mut worklist = Worklist::new();
-    worklist.extend((0..num_blocks).map(BlockId));
+impl Dataflow for LivenessAnalysis {
+    type Block = BlockId;
 
-    while let Some(id) = worklist.pop() {
-        let i = id.0;
+    const DIRECTION: Direction = Direction::Backward;
 
-        block_out[i].clear();
-        for succ in &func.cfg.successors[i] {
-            block_out[i].union(&block_in[succ.0]);
-        }
+    fn boundary(&self) -> BitVector {
+        BitVector::new(self.interner.len())
+    }
 
-        let mut temp = block_out[i].clone();
-        temp.difference(&block_kill[i]);
-        temp.union(&block_gen[i]);
+    fn meet(&self, current: &mut BitVector, other: &BitVector) {
+        current.union(other);
+    }
 
-        if temp != block_in[i] {
-            block_in[i] = temp;
-            worklist.extend(func.cfg.predecessors[i].iter().copied());
-        }
+    fn transfer(&self, input: &BitVector, id: BlockId) -> BitVector {
+        let mut output = input.clone();
+        output.difference(&self.block_kill[id.0]);
+        output.union(&self.block_gen[id.0]);
+        output
     }
+}
+
+pub fn compute_liveness(func: &Function) -> LivenessResult {
+    let liveness = LivenessAnalysis::new(func);
+    let (block_out, _) = solve(&func.cfg, &liveness);
+    let num_gp_variables = liveness.interner.len();
 
     let empty_dataflow_set = || -> Vec<Vec<BitVector>> {
         func.basic_blocks
@@ -109,8 +128,8 @@ pub fn compute_liveness(func: &Function) -> LivenessResult {
 
     for (i, block) in func.basic_blocks.iter().enumerate() {
         for (j, inst) in block.instructions.iter().enumerate().rev() {
-            inst_gen[i][j].set_from(inst.uses().iter().map(|use_| interner[use_]));
-            inst_kill[i][j].set_from(inst.defs().iter().map(|def| interner[def]));
+            inst_gen[i][j].set_from(inst.uses().iter().map(|use_| liveness.interner[use_]));
+            inst_kill[i][j].set_from(inst.defs().iter().map(|def| liveness.interner[def]));
 
             inst_out[i][j] = if j == block.instructions.len() - 1 {
                 block_out[i].clone()
@@ -128,6 +147,6 @@ pub fn compute_liveness(func: &Function) -> LivenessResult {
         block_out,
         inst_in,
         inst_out,
-        interner,
+        interner: liveness.interner,
     }
 }
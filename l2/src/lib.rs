@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::fmt;
 
-use utils::{DisplayResolved, Interner};
+use utils::{BlockIndex, ControlFlow, DisplayResolved, Interner};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, PartialOrd, Ord)]
 pub enum Register {
@@ -226,6 +226,10 @@ pub enum Instruction {
         callee: Value,
         args: i64,
     },
+    TailCall {
+        callee: Value,
+        args: i64,
+    },
     Print,
     Input,
     Allocate,
@@ -256,9 +260,13 @@ impl Instruction {
             | Compare { dst, .. }
             | LEA { dst, .. } => vec![*dst],
 
-            Store { .. } | StoreArithmetic { .. } | CJump { .. } | Label(_) | Goto(_) | Return => {
-                Vec::new()
-            }
+            Store { .. }
+            | StoreArithmetic { .. }
+            | CJump { .. }
+            | Label(_)
+            | Goto(_)
+            | Return
+            | TailCall { .. } => Vec::new(),
 
             Call { .. } | Print | Input | Allocate | TupleError | TensorError(_) => {
                 let caller_save = [R10, R11, R8, R9, RAX, RCX, RDI, RDX, RSI];
@@ -330,6 +338,18 @@ impl Instruction {
                 uses
             }
 
+            TailCall { callee, args } => {
+                let call = Call {
+                    callee: *callee,
+                    args: *args,
+                };
+                let callee_save = [R12, R13, R14, R15, RBP, RBX];
+                call.uses()
+                    .into_iter()
+                    .chain(callee_save.into_iter().map(Value::Register))
+                    .collect()
+            }
+
             Print => vec![Value::Register(RDI)],
 
             Allocate => vec![Value::Register(RDI), Value::Register(RSI)],
@@ -400,7 +420,7 @@ impl Instruction {
             Label(_) | Goto(_) | Return | Print | Input | Allocate | TupleError
             | TensorError(_) => (),
 
-            Call { callee, .. } => {
+            Call { callee, .. } | TailCall { callee, .. } => {
                 replace_helper(callee);
             }
 
@@ -519,6 +539,9 @@ impl DisplayResolved for Instruction {
             Goto(label) => write!(f, "goto :{}", interner.resolve(label.0)),
             Return => write!(f, "return"),
             Call { callee, args } => write!(f, "call {} {}", callee.resolved(interner), args),
+            TailCall { callee, args } => {
+                write!(f, "tail-call {} {}", callee.resolved(interner), args)
+            }
             Print => write!(f, "call print 1"),
             Input => write!(f, "call input 0"),
             Allocate => write!(f, "call allocate 2"),
@@ -561,6 +584,16 @@ impl DisplayResolved for BasicBlock {
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct BlockId(pub usize);
 
+impl BlockIndex for BlockId {
+    fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    fn index(self) -> usize {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: SymbolId,
@@ -584,6 +617,7 @@ impl Function {
                 Instruction::CJump { .. }
                 | Instruction::Goto(_)
                 | Instruction::Return
+                | Instruction::TailCall { .. }
                 | Instruction::TupleError
                 | Instruction::TensorError(_) => {
                     block.instructions.push(inst);
@@ -640,6 +674,22 @@ pub struct ControlFlowGraph {
     pub predecessors: Vec<Vec<BlockId>>,
 }
 
+impl ControlFlow for ControlFlowGraph {
+    type Block = BlockId;
+
+    fn num_blocks(&self) -> usize {
+        self.successors.len()
+    }
+
+    fn successors(&self, block: BlockId) -> &[BlockId] {
+        &self.successors[block.0]
+    }
+
+    fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[block.0]
+    }
+}
+
 impl ControlFlowGraph {
     pub fn new(basic_blocks: &[BasicBlock]) -> Self {
         let label_to_block: HashMap<SymbolId, BlockId> = basic_blocks
@@ -679,6 +729,7 @@ impl ControlFlowGraph {
                 }
 
                 Some(Instruction::Return)
+                | Some(Instruction::TailCall { .. })
                 | Some(Instruction::TupleError)
                 | Some(Instruction::TensorError(_)) => (),
 
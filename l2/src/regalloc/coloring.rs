@@ -2,9 +2,9 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use std::iter;
 
 use l2::*;
-use utils::{BitVector, Interner};
+use utils::{BitVector, Interner, LoopForest};
 
-use crate::analysis::{LivenessResult, LoopForest};
+use crate::analysis::LivenessResult;
 use crate::regalloc::interference::InterferenceGraph;
 
 type ValueId = usize;
@@ -49,7 +49,7 @@ impl<'a, 'b> ColoringAllocator<'a, 'b> {
         func: &Function,
         liveness: &LivenessResult,
         interference: &'a mut InterferenceGraph<'a>,
-        loops: &LoopForest,
+        loops: &LoopForest<BlockId>,
         prev_spilled: &'b HashSet<Value>,
     ) -> Self {
         let interner = func
@@ -116,7 +116,7 @@ impl<'a, 'b> ColoringAllocator<'a, 'b> {
         let alias = (0..num_nodes).collect();
 
         let color = (0..num_nodes)
-            .filter_map(|n| precolored.contains(&n).then_some(n))
+            .filter(|n| precolored.contains(n))
             .map(|n| (n, n))
             .collect();
 
@@ -361,7 +361,7 @@ impl<'a, 'b> ColoringAllocator<'a, 'b> {
                 k += 1;
             }
         }
-        return k < Register::NUM_GP_REGISTERS;
+        k < Register::NUM_GP_REGISTERS
     }
 
     fn get_alias(&self, node: ValueId) -> ValueId {
@@ -467,12 +467,12 @@ impl<'a, 'b> ColoringAllocator<'a, 'b> {
     }
 }
 
-pub fn color_graph<'a, 'b>(
+pub fn color_graph<'a>(
     func: &Function,
     liveness: &LivenessResult,
     interference: &'a mut InterferenceGraph<'a>,
-    loops: &'a LoopForest,
-    prev_spilled: &'b HashSet<Value>,
+    loops: &'a LoopForest<BlockId>,
+    prev_spilled: &HashSet<Value>,
 ) -> ColoringResult {
     let mut allocator = ColoringAllocator::new(func, liveness, interference, loops, prev_spilled);
     allocator.allocate();
@@ -91,7 +91,6 @@ impl<'a> InterferenceGraph<'a> {
 impl DisplayResolved for InterferenceGraph<'_> {
     fn fmt_with(&self, f: &mut fmt::Formatter, interner: &Interner<String>) -> fmt::Result {
         let mut lines: Vec<String> = (0..self.graph.len())
-            .into_iter()
             .map(|i| {
                 let mut line: Vec<String> = self.graph[i]
                     .iter()
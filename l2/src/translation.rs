@@ -1,4 +1,3 @@
-use l1;
 use l2::*;
 use utils::Interner;
 
@@ -178,6 +177,10 @@ fn translate_instruction(
             callee: translate_value(callee, interner),
             args: *args,
         },
+        TailCall { callee, args } => L1::TailCall {
+            callee: translate_value(callee, interner),
+            args: *args,
+        },
         Print => L1::Print,
         Input => L1::Input,
         Allocate => L1::Allocate,
@@ -1,8 +1,21 @@
-mod dataflow;
+mod alias;
+mod available_expr;
+mod call_graph;
 mod def_use;
+mod dominators;
+mod expression;
 mod liveness;
+mod loops;
 mod reaching_def;
+mod very_busy_expr;
 
+pub use alias::may_alias;
+pub use available_expr::compute_available_exprs;
+pub use call_graph::build_call_graph;
 pub use def_use::{DefUseChain, build_def_use};
+pub use dominators::compute_dominators;
+pub use expression::ExpressionResult;
 pub use liveness::{LivenessResult, compute_liveness};
+pub use loops::compute_loops;
 pub use reaching_def::{ReachingDefResult, compute_reaching_def};
+pub use very_busy_expr::compute_very_busy_exprs;
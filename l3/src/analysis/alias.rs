@@ -0,0 +1,56 @@
+use l3::*;
+
+// A base variable together with the point of its definition, if it has one,
+// so that two definitions of the same variable never count as one base.
+type Base<P> = (SymbolId, Option<P>);
+
+/// Whether the addresses held by `a` and `b` at their points may overlap.
+///
+/// `def_of(var, at)` returns the definition of `var` reaching the point `at`
+/// and the point of that definition. Both addresses are followed back through
+/// copies and constant additions; they are disjoint only when they end at the
+/// same base with offsets at least a word apart.
+pub fn may_alias<'a, P: Copy + PartialEq>(
+    def_of: impl Fn(SymbolId, P) -> Option<(P, &'a Instruction)> + Copy,
+    (at_a, a): (P, SymbolId),
+    (at_b, b): (P, SymbolId),
+) -> bool {
+    let (base_a, offset_a) = resolve_address(def_of, at_a, a);
+    let (base_b, offset_b) = resolve_address(def_of, at_b, b);
+    base_a != base_b || offset_a.abs_diff(offset_b) < 8
+}
+
+fn resolve_address<'a, P: Copy>(
+    def_of: impl Fn(SymbolId, P) -> Option<(P, &'a Instruction)> + Copy,
+    at: P,
+    var: SymbolId,
+) -> (Base<P>, i64) {
+    let Some((def, inst)) = def_of(var, at) else {
+        return ((var, None), 0);
+    };
+
+    match inst {
+        Instruction::Assign {
+            src: Value::Variable(src),
+            ..
+        } => resolve_address(def_of, def, *src),
+
+        Instruction::Binary {
+            lhs: Value::Variable(base),
+            op: BinaryOp::Add,
+            rhs: Value::Number(offset),
+            ..
+        }
+        | Instruction::Binary {
+            lhs: Value::Number(offset),
+            op: BinaryOp::Add,
+            rhs: Value::Variable(base),
+            ..
+        } => {
+            let (base, base_offset) = resolve_address(def_of, def, *base);
+            (base, base_offset.wrapping_add(*offset))
+        }
+
+        _ => ((var, Some(def)), 0),
+    }
+}
@@ -0,0 +1,38 @@
+use l3::*;
+use utils::{BitVector, Dataflow, Direction};
+
+use crate::analysis::expression::{ExpressionResult, LocalSets};
+
+#[derive(Debug)]
+struct AvailableExprAnalysis(LocalSets);
+
+impl Dataflow for AvailableExprAnalysis {
+    type Block = BlockId;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    fn boundary(&self) -> BitVector {
+        BitVector::new(self.0.num_exprs)
+    }
+
+    fn top(&self) -> BitVector {
+        let mut all = BitVector::new(self.0.num_exprs);
+        all.set_from(0..self.0.num_exprs);
+        all
+    }
+
+    fn meet(&self, current: &mut BitVector, other: &BitVector) {
+        current.intersection(other);
+    }
+
+    fn transfer(&self, input: &BitVector, id: BlockId) -> BitVector {
+        let mut output = input.clone();
+        output.difference(&self.0.block_kill[id.0]);
+        output.union(&self.0.block_gen[id.0]);
+        output
+    }
+}
+
+pub fn compute_available_exprs(func: &Function) -> ExpressionResult {
+    ExpressionResult::compute(func, AvailableExprAnalysis)
+}
@@ -0,0 +1,91 @@
+use l3::*;
+use utils::CallGraph;
+
+pub fn build_call_graph(prog: &Program) -> CallGraph {
+    let mut call_graph = CallGraph::new(prog.functions.iter().map(|func| func.name.0).collect());
+
+    for (i, func) in prog.functions.iter().enumerate() {
+        for inst in func
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+        {
+            match inst {
+                Instruction::Call { callee, .. }
+                | Instruction::CallResult { callee, .. }
+                | Instruction::TailCall { callee, .. } => match callee {
+                    Callee::Value(Value::Function(name)) => call_graph.add_call(i, name.0),
+                    Callee::Value(_) => call_graph.add_indirect_call(i),
+                    _ => (),
+                },
+                _ => (),
+            }
+
+            for operand in inst.operands() {
+                if let Value::Function(name) = operand {
+                    call_graph.add_reference(i, name.0);
+                }
+            }
+        }
+    }
+
+    call_graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::parse;
+
+    const PROGRAM: &str = r"
+define @main() {
+    %x <- call @even(4)
+    %f <- @odd
+    %y <- call %f(3)
+    return
+}
+
+define @even(%n) {
+    %r <- call @odd(%n)
+    return %r
+}
+
+define @odd(%n) {
+    %r <- call @even(%n)
+    return %r
+}
+
+define @unused() {
+    return 1
+}
+";
+
+    #[test]
+    fn records_calls_and_references() {
+        let prog = parse(PROGRAM);
+        let call_graph = build_call_graph(&prog);
+        let [main, even, odd, unused] = [0, 1, 2, 3];
+
+        assert_eq!(call_graph.callees(main), [even]);
+        assert_eq!(call_graph.references(main), [odd]);
+        assert!(call_graph.has_indirect_calls(main));
+        assert!(!call_graph.has_indirect_calls(even));
+        assert_eq!(call_graph.callees(odd), [even]);
+        assert!(call_graph.callees(unused).is_empty());
+
+        let reachable = call_graph.reachable_from(main);
+        assert!(reachable.test(odd));
+        assert!(!reachable.test(unused));
+    }
+
+    #[test]
+    fn orders_callee_sccs_first() {
+        let prog = parse(PROGRAM);
+        let sccs = build_call_graph(&prog).sccs();
+        let position = |func| sccs.iter().position(|scc| scc.contains(&func)).unwrap();
+
+        assert_eq!(sccs.len(), 3);
+        assert_eq!(position(1), position(2));
+        assert!(position(1) < position(0));
+    }
+}
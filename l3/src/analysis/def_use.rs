@@ -7,36 +7,42 @@ use utils::{DisplayResolved, Interner};
 
 use crate::analysis::ReachingDefResult;
 
+type Position = (BlockId, usize);
+
 #[derive(Debug)]
 pub struct DefUseChain<'a> {
-    interner: &'a Interner<Instruction>,
-    users: Vec<HashSet<&'a Instruction>>,
+    func: &'a Function,
+    reaching_def: &'a ReachingDefResult,
+    users: Vec<HashSet<Position>>,
 }
 
 impl<'a> DefUseChain<'a> {
     pub fn new(func: &'a Function, reaching_def: &'a ReachingDefResult) -> Self {
-        let interner = &reaching_def.interner;
-        let num_insts = interner.len();
+        let num_insts = reaching_def.interner.len();
         let mut users = vec![HashSet::new(); num_insts];
 
         for (i, block) in func.basic_blocks.iter().enumerate() {
             for (j, inst) in block.instructions.iter().enumerate() {
                 for use_ in inst.uses() {
                     for def_id in &reaching_def.in_[i][j] {
-                        if interner.resolve(def_id).defs() == Some(use_) {
-                            users[def_id].insert(inst);
+                        if reaching_def.insts[def_id].defs() == Some(use_) {
+                            users[def_id].insert((block.id, j));
                         }
                     }
                 }
             }
         }
 
-        Self { interner, users }
+        Self {
+            func,
+            reaching_def,
+            users,
+        }
     }
 
-    pub fn is_only_user(&self, inst: &Instruction, user: &Instruction) -> bool {
-        let users = &self.users[self.interner[inst]];
-        users.len() == 1 && users.iter().next() == Some(&user)
+    pub fn is_only_user(&self, def: Position, user: Position) -> bool {
+        let users = &self.users[self.reaching_def.interner[&def]];
+        users.len() == 1 && users.contains(&user)
     }
 }
 
@@ -48,11 +54,14 @@ impl DisplayResolved for DefUseChain<'_> {
             .enumerate()
             .map(|(i, users)| {
                 let mut line: Vec<String> =
-                    iter::once(self.interner.resolve(i).resolved(interner).to_string())
-                        .chain(users.iter().map(|user| user.resolved(interner).to_string()))
+                    iter::once(self.reaching_def.insts[i].resolved(interner).to_string())
+                        .chain(users.iter().map(|&(block, j)| {
+                            let user = &self.func.basic_blocks[block.0].instructions[j];
+                            user.resolved(interner).to_string()
+                        }))
                         .collect();
                 line.sort();
-                format!("{}", line.join(", "))
+                line.join(", ").to_string()
             })
             .collect();
         lines.sort();
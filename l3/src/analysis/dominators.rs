@@ -0,0 +1,106 @@
+use l3::*;
+use utils::DominatorTree;
+
+pub fn compute_dominators(func: &Function) -> DominatorTree<BlockId> {
+    DominatorTree::new(&func.cfg)
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::LoopForest;
+
+    use super::*;
+    use crate::testing::parse;
+
+    #[test]
+    fn finds_immediate_dominators_and_frontiers() {
+        let prog = parse(
+            r"
+define @f(%a) {
+    %c <- %a < 5
+    br %c :then
+    %a <- %a + 1
+    br :join
+    :then
+    %a <- %a + 2
+    :join
+    %d <- %a < 9
+    br %d :join
+    return %a
+}
+",
+        );
+        let func = &prog.functions[0];
+        let dt = compute_dominators(func);
+        let [entry, other, then, join, exit] = [0, 1, 2, 3, 4].map(BlockId);
+
+        assert_eq!(dt.idom(entry), None);
+        for block in [other, then, join] {
+            assert_eq!(dt.idom(block), Some(entry));
+        }
+        assert_eq!(dt.idom(exit), Some(join));
+
+        assert!(dt.dominates(join, exit));
+        assert!(!dt.dominates(then, join));
+
+        assert_eq!(dt.frontier(other), [join]);
+        assert_eq!(dt.frontier(then), [join]);
+        assert_eq!(dt.frontier(join), [join]);
+        assert!(dt.frontier(entry).is_empty());
+    }
+
+    #[test]
+    fn leaves_unreachable_blocks_out() {
+        let prog = parse(
+            r"
+define @f() {
+    return 1
+    :dead
+    return 3
+}
+",
+        );
+        let dt = compute_dominators(&prog.functions[0]);
+        assert!(dt.is_reachable(BlockId(0)));
+        assert!(!dt.is_reachable(BlockId(1)));
+    }
+
+    #[test]
+    fn nests_loops_by_header() {
+        let prog = parse(
+            r"
+define @f(%n) {
+    %i <- 0
+    :outer
+    %j <- 0
+    :inner
+    %j <- %j + 1
+    %c <- %j < %n
+    br %c :inner
+    %i <- %i + 1
+    %d <- %i < %n
+    br %d :outer
+    return %i
+}
+",
+        );
+        let func = &prog.functions[0];
+        let loops = LoopForest::new(&func.cfg, &compute_dominators(func));
+        let [entry, outer, inner, latch, exit] = [0, 1, 2, 3, 4].map(BlockId);
+
+        assert_eq!(loops.loops().len(), 2);
+        let outer_loop = loops.loops().iter().find(|l| l.header() == outer).unwrap();
+        let inner_loop = loops.loops().iter().find(|l| l.header() == inner).unwrap();
+
+        assert!(outer_loop.contains(inner) && outer_loop.contains(latch));
+        assert!(!outer_loop.contains(entry) && !outer_loop.contains(exit));
+        assert_eq!(inner_loop.basic_blocks(), [inner]);
+
+        assert_eq!(outer_loop.depth(), 1);
+        assert_eq!(inner_loop.depth(), 2);
+        assert!(inner_loop.parent().is_some());
+        assert_eq!(loops.loop_depth(inner), 2);
+        assert_eq!(loops.loop_depth(latch), 1);
+        assert_eq!(loops.loop_depth(exit), 0);
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use l3::*;
+use utils::{BitVector, Dataflow, Direction, DisplayResolved, Interner, solve};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expression {
+    Binary(Value, BinaryOp, Value),
+    Compare(Value, CompareOp, Value),
+}
+
+impl Expression {
+    pub fn of(inst: &Instruction) -> Option<Self> {
+        match inst {
+            Instruction::Binary { lhs, op, rhs, .. } => Some(Self::Binary(*lhs, op.clone(), *rhs)),
+            Instruction::Compare { lhs, cmp, rhs, .. } => {
+                Some(Self::Compare(*lhs, cmp.clone(), *rhs))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_instruction(&self, dst: SymbolId) -> Instruction {
+        match self.clone() {
+            Self::Binary(lhs, op, rhs) => Instruction::Binary { dst, lhs, op, rhs },
+            Self::Compare(lhs, cmp, rhs) => Instruction::Compare { dst, lhs, cmp, rhs },
+        }
+    }
+
+    pub fn uses(&self) -> Vec<SymbolId> {
+        let (Self::Binary(lhs, _, rhs) | Self::Compare(lhs, _, rhs)) = self;
+        [lhs, rhs]
+            .into_iter()
+            .filter_map(|val| match val {
+                Value::Variable(var) => Some(*var),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl DisplayResolved for Expression {
+    fn fmt_with(&self, f: &mut fmt::Formatter, interner: &Interner<String>) -> fmt::Result {
+        match self {
+            Self::Binary(lhs, op, rhs) => write!(
+                f,
+                "{} {} {}",
+                lhs.resolved(interner),
+                op,
+                rhs.resolved(interner)
+            ),
+            Self::Compare(lhs, cmp, rhs) => write!(
+                f,
+                "{} {} {}",
+                lhs.resolved(interner),
+                cmp,
+                rhs.resolved(interner)
+            ),
+        }
+    }
+}
+
+// Every expression computed in a function, and for each variable the
+// expressions that a definition of it invalidates.
+#[derive(Debug)]
+pub struct ExpressionTable {
+    pub interner: Interner<Expression>,
+    pub kills: HashMap<SymbolId, Vec<usize>>,
+}
+
+impl ExpressionTable {
+    pub fn new(func: &Function) -> Self {
+        let mut interner = Interner::new();
+        let mut kills: HashMap<SymbolId, Vec<usize>> = HashMap::new();
+
+        for inst in func
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+        {
+            let Some(expr) = Expression::of(inst) else {
+                continue;
+            };
+            if interner.get(&expr).is_some() {
+                continue;
+            }
+
+            let uses = expr.uses();
+            let k = interner.intern(expr);
+            for var in uses {
+                let killed = kills.entry(var).or_default();
+                if !killed.contains(&k) {
+                    killed.push(k);
+                }
+            }
+        }
+
+        Self { interner, kills }
+    }
+
+    pub fn killed_by(&self, inst: &Instruction) -> impl Iterator<Item = usize> {
+        inst.defs()
+            .and_then(|def| self.kills.get(&def))
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    pub fn computed_by(&self, inst: &Instruction) -> Option<usize> {
+        Expression::of(inst).map(|expr| self.interner[&expr])
+    }
+
+    // Passes `set` through `inst` in the direction of an analysis: a forward
+    // one sees the computation before the definition, a backward one after.
+    pub fn transfer(&self, set: &mut BitVector, inst: &Instruction, direction: Direction) {
+        match direction {
+            Direction::Forward => {
+                set.set_from(self.computed_by(inst).into_iter());
+                set.reset_from(self.killed_by(inst));
+            }
+            Direction::Backward => {
+                set.reset_from(self.killed_by(inst));
+                set.set_from(self.computed_by(inst).into_iter());
+            }
+        }
+    }
+}
+
+// The expressions each block generates and kills when walked in the
+// direction of an analysis.
+#[derive(Debug)]
+pub struct LocalSets {
+    pub num_exprs: usize,
+    pub block_gen: Vec<BitVector>,
+    pub block_kill: Vec<BitVector>,
+}
+
+impl LocalSets {
+    pub fn new(func: &Function, table: &ExpressionTable, direction: Direction) -> Self {
+        let num_exprs = table.interner.len();
+        let mut block_gen = Vec::with_capacity(func.basic_blocks.len());
+        let mut block_kill = Vec::with_capacity(func.basic_blocks.len());
+
+        for block in &func.basic_blocks {
+            let mut gen_ = BitVector::new(num_exprs);
+            let mut kill = BitVector::new(num_exprs);
+
+            for inst in in_direction(&block.instructions, direction) {
+                table.transfer(&mut gen_, inst, direction);
+                kill.set_from(table.killed_by(inst));
+            }
+
+            block_gen.push(gen_);
+            block_kill.push(kill);
+        }
+
+        LocalSets {
+            num_exprs,
+            block_gen,
+            block_kill,
+        }
+    }
+}
+
+fn in_direction(
+    instructions: &[Instruction],
+    direction: Direction,
+) -> Box<dyn Iterator<Item = &Instruction> + '_> {
+    match direction {
+        Direction::Forward => Box::new(instructions.iter()),
+        Direction::Backward => Box::new(instructions.iter().rev()),
+    }
+}
+
+#[derive(Debug)]
+pub struct ExpressionResult {
+    pub table: ExpressionTable,
+    pub in_: Vec<Vec<BitVector>>,
+    pub out: Vec<Vec<BitVector>>,
+}
+
+impl ExpressionResult {
+    // Solves the analysis built by `analysis` from the local sets of `func`
+    // and spreads its block sets over the instructions.
+    pub fn compute<D>(func: &Function, analysis: impl FnOnce(LocalSets) -> D) -> Self
+    where
+        D: Dataflow<Block = BlockId>,
+    {
+        let table = ExpressionTable::new(func);
+        let dataflow = analysis(LocalSets::new(func, &table, D::DIRECTION));
+        let (block_enter, _) = solve(&func.cfg, &dataflow);
+
+        let mut in_ = Vec::with_capacity(func.basic_blocks.len());
+        let mut out = Vec::with_capacity(func.basic_blocks.len());
+
+        for (block, mut set) in func.basic_blocks.iter().zip(block_enter) {
+            let mut enter = Vec::with_capacity(block.instructions.len());
+            let mut exit = Vec::with_capacity(block.instructions.len());
+
+            for inst in in_direction(&block.instructions, D::DIRECTION) {
+                enter.push(set.clone());
+                table.transfer(&mut set, inst, D::DIRECTION);
+                exit.push(set.clone());
+            }
+
+            match D::DIRECTION {
+                Direction::Forward => {
+                    in_.push(enter);
+                    out.push(exit);
+                }
+                Direction::Backward => {
+                    exit.reverse();
+                    enter.reverse();
+                    in_.push(exit);
+                    out.push(enter);
+                }
+            }
+        }
+
+        ExpressionResult { table, in_, out }
+    }
+}
+
+impl DisplayResolved for ExpressionResult {
+    fn fmt_with(&self, f: &mut fmt::Formatter, interner: &Interner<String>) -> fmt::Result {
+        for (in_, out) in self.in_.iter().flatten().zip(self.out.iter().flatten()) {
+            for (name, bitvec) in [("IN", in_), ("OUT", out)] {
+                let mut lines: Vec<String> = bitvec
+                    .iter()
+                    .map(|k| format!("{}\n", self.table.interner.resolve(k).resolved(interner)))
+                    .collect();
+                lines.sort();
+                writeln!(f, "{}\n{{\n{}}}", name, lines.join(""))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{compute_available_exprs, compute_very_busy_exprs};
+    use crate::testing::parse;
+
+    fn names(
+        result: &ExpressionResult,
+        set: &BitVector,
+        interner: &Interner<String>,
+    ) -> Vec<String> {
+        let mut names: Vec<String> = set
+            .iter()
+            .map(|k| {
+                result
+                    .table
+                    .interner
+                    .resolve(k)
+                    .resolved(interner)
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn available_expressions_are_killed_on_any_path() {
+        let prog = parse(
+            r"
+define @f(%a, %b) {
+    %x <- %a + %b
+    %c <- %a < 5
+    br %c :join
+    %a <- 3
+    :join
+    %y <- %a + %b
+    return %y
+}
+",
+        );
+        let result = compute_available_exprs(&prog.functions[0]);
+
+        assert_eq!(
+            names(&result, &result.out[0][2], &prog.interner),
+            ["%a + %b", "%a < 5"]
+        );
+        assert!(names(&result, &result.in_[2][1], &prog.interner).is_empty());
+        assert_eq!(
+            names(&result, &result.out[2][1], &prog.interner),
+            ["%a + %b"]
+        );
+    }
+
+    #[test]
+    fn very_busy_expressions_are_computed_on_every_path() {
+        let prog = parse(
+            r"
+define @f(%a, %b, %c) {
+    br %c :other
+    %x <- %a + %b
+    %z <- %a * 2
+    return %x
+    :other
+    %y <- %a + %b
+    %a <- 1
+    %w <- %a * 2
+    return %y
+}
+",
+        );
+        let result = compute_very_busy_exprs(&prog.functions[0]);
+
+        assert_eq!(
+            names(&result, &result.in_[0][0], &prog.interner),
+            ["%a + %b"]
+        );
+        assert_eq!(
+            names(&result, &result.in_[1][0], &prog.interner),
+            ["%a * 2", "%a + %b"]
+        );
+        assert!(names(&result, &result.out[2][1], &prog.interner).is_empty());
+    }
+}
@@ -1,13 +1,12 @@
 use std::fmt;
 
 use l3::*;
-use utils::{BitVector, DisplayResolved, Interner};
-
-use crate::analysis::dataflow::{Dataflow, Direction, solve};
+use utils::{BitVector, Dataflow, Direction, DisplayResolved, Interner, solve};
 
 #[derive(Debug)]
 pub struct LivenessResult {
     pub interner: Interner<SymbolId>,
+    pub in_: Vec<Vec<BitVector>>,
     pub out: Vec<Vec<BitVector>>,
 }
 
@@ -15,6 +14,10 @@ impl LivenessResult {
     pub fn is_dead_at(&self, b: BlockId, i: usize, var: SymbolId) -> bool {
         !self.out[b.0][i].test(self.interner[&var])
     }
+
+    pub fn is_live_in(&self, b: BlockId, var: SymbolId) -> bool {
+        self.in_[b.0][0].test(self.interner[&var])
+    }
 }
 
 impl DisplayResolved for LivenessResult {
@@ -80,6 +83,8 @@ impl LivenessAnalysis {
 }
 
 impl Dataflow for LivenessAnalysis {
+    type Block = BlockId;
+
     const DIRECTION: Direction = Direction::Backward;
 
     fn boundary(&self) -> BitVector {
@@ -87,7 +92,7 @@ impl Dataflow for LivenessAnalysis {
     }
 
     fn meet(&self, current: &mut BitVector, other: &BitVector) {
-        current.union(&other);
+        current.union(other);
     }
 
     fn transfer(&self, input: &BitVector, id: BlockId) -> BitVector {
@@ -100,7 +105,7 @@ impl Dataflow for LivenessAnalysis {
 
 pub fn compute_liveness(func: &Function) -> LivenessResult {
     let liveness = LivenessAnalysis::new(func);
-    let (block_out, _) = solve(func, &liveness);
+    let (block_out, _) = solve(&func.cfg, &liveness);
 
     let empty_dataflow_set = || -> Vec<Vec<BitVector>> {
         func.basic_blocks
@@ -128,6 +133,7 @@ pub fn compute_liveness(func: &Function) -> LivenessResult {
 
     LivenessResult {
         interner: liveness.interner,
+        in_: inst_in,
         out: inst_out,
     }
 }
@@ -0,0 +1,6 @@
+use l3::*;
+use utils::{DominatorTree, LoopForest};
+
+pub fn compute_loops(func: &Function, dt: &DominatorTree<BlockId>) -> LoopForest<BlockId> {
+    LoopForest::new(&func.cfg, dt)
+}
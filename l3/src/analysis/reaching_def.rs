@@ -2,15 +2,19 @@ use std::collections::HashMap;
 use std::fmt;
 
 use l3::*;
-use utils::{BitVector, DisplayResolved, Interner};
-
-use crate::analysis::dataflow::{Dataflow, Direction, solve};
+use utils::{BitVector, Dataflow, Direction, DisplayResolved, Interner, solve};
 
 type InstId = usize;
 
+type Position = (BlockId, usize);
+
+// Definitions are identified by their position, so that two textually equal
+// instructions are still different definitions. Parameters are defined in a
+// block placed after the last one of the function.
 #[derive(Debug)]
 pub struct ReachingDefResult {
-    pub interner: Interner<Instruction>,
+    pub interner: Interner<Position>,
+    pub insts: Vec<Instruction>,
     pub in_: Vec<Vec<BitVector>>,
 }
 
@@ -20,7 +24,7 @@ impl DisplayResolved for ReachingDefResult {
             for bitvec in vec {
                 let mut lines: Vec<String> = bitvec
                     .iter()
-                    .map(|k| format!("{}\n", self.interner.resolve(k).resolved(interner)))
+                    .map(|k| format!("{}\n", self.insts[k].resolved(interner)))
                     .collect();
                 lines.sort();
                 writeln!(f, "IN\n{{\n{}}}", lines.join(""))?;
@@ -32,7 +36,8 @@ impl DisplayResolved for ReachingDefResult {
 
 #[derive(Debug)]
 struct ReachingDefAnalysis {
-    interner: Interner<Instruction>,
+    interner: Interner<Position>,
+    insts: Vec<Instruction>,
     def_table: HashMap<SymbolId, Vec<InstId>>,
     block_gen: Vec<BitVector>,
     block_kill: Vec<BitVector>,
@@ -40,21 +45,19 @@ struct ReachingDefAnalysis {
 
 impl ReachingDefAnalysis {
     pub fn new(func: &Function) -> Self {
-        let (interner, def_table) = func
-            .basic_blocks
-            .iter()
-            .flat_map(|block| &block.instructions)
-            .filter(|inst| inst.defs().is_some())
-            .fold(
-                (Interner::new(), HashMap::new()),
-                |(mut interner, mut def_table), inst| {
-                    let index = interner.intern(inst.clone());
-                    if let Some(def) = inst.defs() {
-                        def_table.entry(def).or_insert(Vec::new()).push(index);
-                    }
-                    (interner, def_table)
-                },
-            );
+        let mut interner = Interner::new();
+        let mut insts = Vec::new();
+        let mut def_table: HashMap<SymbolId, Vec<InstId>> = HashMap::new();
+
+        for block in &func.basic_blocks {
+            for (j, inst) in block.instructions.iter().enumerate() {
+                if let Some(def) = inst.defs() {
+                    let index = interner.intern((block.id, j));
+                    insts.push(inst.clone());
+                    def_table.entry(def).or_default().push(index);
+                }
+            }
+        }
 
         let num_insts = interner.len();
         let num_blocks = func.basic_blocks.len();
@@ -65,10 +68,11 @@ impl ReachingDefAnalysis {
             block
                 .instructions
                 .iter()
+                .enumerate()
                 .rev()
-                .filter_map(|inst| inst.defs().and_then(|def| Some((inst, def))))
-                .for_each(|(inst, def)| {
-                    let j = interner[inst];
+                .filter_map(|(k, inst)| inst.defs().map(|def| (k, def)))
+                .for_each(|(k, def)| {
+                    let j = interner[&(block.id, k)];
                     if !block_kill[i].test(j) {
                         block_gen[i].set(j);
                     }
@@ -82,6 +86,7 @@ impl ReachingDefAnalysis {
 
         ReachingDefAnalysis {
             interner,
+            insts,
             def_table,
             block_gen,
             block_kill,
@@ -90,6 +95,8 @@ impl ReachingDefAnalysis {
 }
 
 impl Dataflow for ReachingDefAnalysis {
+    type Block = BlockId;
+
     const DIRECTION: Direction = Direction::Forward;
 
     fn boundary(&self) -> BitVector {
@@ -97,7 +104,7 @@ impl Dataflow for ReachingDefAnalysis {
     }
 
     fn meet(&self, current: &mut BitVector, other: &BitVector) {
-        current.union(&other);
+        current.union(other);
     }
 
     fn transfer(&self, input: &BitVector, id: BlockId) -> BitVector {
@@ -131,7 +138,7 @@ pub fn compute_reaching_def(func: &Function) -> ReachingDefResult {
     }
 
     let reaching_def = ReachingDefAnalysis::new(&func_clone);
-    let (block_in, _) = solve(&func_clone, &reaching_def);
+    let (block_in, _) = solve(&func_clone.cfg, &reaching_def);
 
     let empty_dataflow_set = || -> Vec<Vec<BitVector>> {
         func_clone
@@ -156,7 +163,7 @@ pub fn compute_reaching_def(func: &Function) -> ReachingDefResult {
 
             inst_out[i][j] = inst_in[i][j].clone();
             if let Some(def) = inst.defs() {
-                let k = reaching_def.interner[inst];
+                let k = reaching_def.interner[&(block.id, j)];
                 inst_out[i][j].reset_from(
                     reaching_def.def_table[&def]
                         .iter()
@@ -169,6 +176,7 @@ pub fn compute_reaching_def(func: &Function) -> ReachingDefResult {
 
     ReachingDefResult {
         interner: reaching_def.interner,
+        insts: reaching_def.insts,
         in_: inst_in,
     }
 }
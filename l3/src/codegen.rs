@@ -0,0 +1,33 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use l3::*;
+
+use crate::translation::translate_program;
+
+struct CodeGenerator {
+    stream: BufWriter<File>,
+}
+
+impl CodeGenerator {
+    pub fn new() -> io::Result<Self> {
+        let file = File::create("prog.L2")?;
+        Ok(Self {
+            stream: BufWriter::new(file),
+        })
+    }
+
+    pub fn emit_program(&mut self, prog: &Program, verbose: bool) -> io::Result<()> {
+        write!(self.stream, "{}", translate_program(prog, verbose))
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+pub fn generate_code(prog: &Program, verbose: bool) -> io::Result<()> {
+    let mut code_generator = CodeGenerator::new()?;
+    code_generator.emit_program(prog, verbose)?;
+    code_generator.finish()
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::Interner;
+
+pub fn unique_prefix(interner: &Interner<String>) -> String {
+    (0..interner.len())
+        .map(|i| interner.resolve(i))
+        .max_by_key(|name| name.len())
+        .map_or_else(String::new, |name| format!("{}_", name))
+}
+
+pub fn fresh_symbol(interner: &mut Interner<String>, base: &str) -> SymbolId {
+    SymbolId(interner.fresh(base))
+}
+
+pub fn globalize_labels(prog: &mut Program) {
+    let prefix = unique_prefix(&prog.interner);
+
+    for (i, func) in prog.functions.iter_mut().enumerate() {
+        let mut renamed: HashMap<SymbolId, SymbolId> = HashMap::new();
+        let mut rename = |label: &mut SymbolId| {
+            *label = *renamed.entry(*label).or_insert_with(|| {
+                let name = format!("{}{}_{}", prefix, i, prog.interner.resolve(label.0));
+                SymbolId(prog.interner.intern(name))
+            });
+        };
+
+        for inst in func
+            .basic_blocks
+            .iter_mut()
+            .flat_map(|block| &mut block.instructions)
+        {
+            match inst {
+                Instruction::Label(label)
+                | Instruction::Branch(label)
+                | Instruction::BranchCond { label, .. }
+                | Instruction::Assign {
+                    src: Value::Label(label),
+                    ..
+                }
+                | Instruction::Store {
+                    src: Value::Label(label),
+                    ..
+                } => rename(label),
+                _ => (),
+            }
+        }
+    }
+}
@@ -4,4 +4,4 @@ mod tiling;
 
 pub use contexts::create_contexts;
 pub use forest::generate_forests;
-pub use tiling::greedy_match;
+pub use tiling::optimal_match;
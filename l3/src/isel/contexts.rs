@@ -30,13 +30,15 @@ pub fn create_contexts(func: &Function) -> Vec<Context> {
                     });
                 }
 
-                Instruction::Label(_)
-                | Instruction::Call { .. }
-                | Instruction::CallResult { .. } => contexts.push(Context {
+                Instruction::Label(_) => contexts.push(Context {
                     block_id: block.id,
                     inst_ids: Vec::new(),
                 }),
 
+                Instruction::Call { .. }
+                | Instruction::CallResult { .. }
+                | Instruction::TailCall { .. } => (),
+
                 _ => context.inst_ids.push(i),
             }
         }
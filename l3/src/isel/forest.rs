@@ -3,7 +3,7 @@ use std::fmt;
 use l3::*;
 use utils::{DisplayResolved, Interner};
 
-use crate::analysis::{DefUseChain, LivenessResult};
+use crate::analysis::{DefUseChain, LivenessResult, may_alias};
 use crate::isel::contexts::Context;
 
 type NodeId = usize;
@@ -90,6 +90,7 @@ impl DisplayResolved for SFNode {
 pub struct SelectionForest {
     pub arena: Vec<SFNode>,
     pub roots: Vec<NodeId>,
+    members: Vec<Vec<usize>>,
 }
 
 impl SelectionForest {
@@ -99,6 +100,7 @@ impl SelectionForest {
         let mut forest = Self {
             arena: Vec::new(),
             roots: Vec::new(),
+            members: ctx.inst_ids.iter().map(|&id| vec![id]).collect(),
         };
 
         for &id in &ctx.inst_ids {
@@ -141,7 +143,7 @@ impl SelectionForest {
                 BranchCond { cond, label } => {
                     forest.make_root(OpKind::Branch, [*cond, Value::Label(*label)], None)
                 }
-                Label(_) | Call { .. } | CallResult { .. } => {
+                Label(_) | Call { .. } | CallResult { .. } | TailCall { .. } | Phi { .. } => {
                     unreachable!("illegal context instruction")
                 }
             }
@@ -206,6 +208,13 @@ impl SelectionForest {
         self.roots.push(op);
     }
 
+    /// Sinks tree `i` into the leaf of tree `j` that reads its result.
+    ///
+    /// The result must die at `j` and `j` must be its only user. Every instruction of tree `i`
+    /// is then checked against everything it would move past: the trees rooted between `i` and
+    /// `j`, the calls between them, and the rest of tree `j`. Two instructions conflict when one
+    /// defines a variable the other uses or defines, when a load would cross a store that may
+    /// alias it, or when a load would cross a call.
     fn try_merge(
         &mut self,
         func: &Function,
@@ -232,34 +241,30 @@ impl SelectionForest {
         };
 
         let block = &func.basic_blocks[ctx.block_id.0];
-        let inst1 = &block.instructions[ctx.inst_ids[i]];
-        let inst2 = &block.instructions[ctx.inst_ids[j]];
+        let start = ctx.inst_ids[i];
+        let end = ctx.inst_ids[j];
 
-        if !liveness.is_dead_at(ctx.block_id, ctx.inst_ids[j], result)
-            || !def_use.is_only_user(inst1, inst2)
+        if !liveness.is_dead_at(ctx.block_id, end, result)
+            || !def_use.is_only_user((ctx.block_id, start), (ctx.block_id, end))
         {
             return false;
         }
 
-        for k in i + 1..j {
-            let mid = &block.instructions[ctx.inst_ids[k]];
-
-            match inst1 {
-                Instruction::Load { .. } => {
-                    if matches!(mid, Instruction::Load { .. })
-                        || matches!(mid, Instruction::Store { .. })
-                    {
-                        return false;
-                    }
-                }
-                _ => {
-                    if mid.uses().iter().any(|use_| inst1.defs() == Some(*use_))
-                        || inst1.uses().iter().any(|use_| mid.defs() == Some(*use_))
-                    {
-                        return false;
-                    }
-                }
-            }
+        let calls = (start + 1..end).filter(|&k| {
+            matches!(
+                block.instructions[k],
+                Instruction::Call { .. } | Instruction::CallResult { .. }
+            )
+        });
+        let mut passed = self.members[i + 1..=j]
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&k| k != end)
+            .chain(calls);
+
+        if passed.any(|k| self.members[i].iter().any(|&m| conflicts(block, m, k))) {
+            return false;
         }
 
         let leaf_parent = self.arena[leaf]
@@ -279,6 +284,8 @@ impl SelectionForest {
         self.arena[u].parent = Some(leaf_parent);
         self.roots.remove(i);
         ctx.inst_ids.remove(i);
+        let members = self.members.remove(i);
+        self.members[j - 1].extend(members);
 
         true
     }
@@ -327,6 +334,38 @@ impl DisplayResolved for SelectionForest {
     }
 }
 
+fn conflicts(block: &BasicBlock, a: usize, b: usize) -> bool {
+    let inst_a = &block.instructions[a];
+    let inst_b = &block.instructions[b];
+    let def_a = inst_a.defs();
+    let def_b = inst_b.defs();
+
+    if inst_b.uses().iter().any(|&use_| def_a == Some(use_))
+        || inst_a.uses().iter().any(|&use_| def_b == Some(use_))
+        || (def_a.is_some() && def_a == def_b)
+    {
+        return true;
+    }
+
+    match (inst_a, inst_b) {
+        (Instruction::Load { src, .. }, Instruction::Store { dst, .. }) => {
+            may_alias(|var, pos| def_before(block, var, pos), (a, *src), (b, *dst))
+        }
+        (Instruction::Load { .. }, Instruction::Call { .. } | Instruction::CallResult { .. }) => {
+            true
+        }
+        _ => false,
+    }
+}
+
+// The definition of `var` closest above position `pos` of the block.
+fn def_before(block: &BasicBlock, var: SymbolId, pos: usize) -> Option<(usize, &Instruction)> {
+    (0..pos)
+        .rev()
+        .find(|&k| block.instructions[k].defs() == Some(var))
+        .map(|k| (k, &block.instructions[k]))
+}
+
 pub fn generate_forests(
     func: &Function,
     liveness: &LivenessResult,
@@ -342,3 +381,106 @@ pub fn generate_forests(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{build_def_use, compute_liveness, compute_reaching_def};
+    use crate::isel::create_contexts;
+    use crate::testing::parse;
+
+    fn num_roots(source: &str) -> usize {
+        let prog = parse(source);
+        let func = &prog.functions[0];
+        let liveness = compute_liveness(func);
+        let reaching_def = compute_reaching_def(func);
+        let def_use = build_def_use(func, &reaching_def);
+        let mut contexts = create_contexts(func);
+
+        generate_forests(func, &liveness, &def_use, &mut contexts)
+            .iter()
+            .map(|forest| forest.roots.len())
+            .sum()
+    }
+
+    #[test]
+    fn load_does_not_cross_aliasing_store() {
+        let source = r"
+define @f(%p) {
+    %q <- %p + 4
+    %v <- load %p
+    store %q <- 5
+    return %v
+}
+";
+        // the address merges into the store, the load stays on its own
+        assert_eq!(num_roots(source), 3);
+    }
+
+    #[test]
+    fn load_crosses_disjoint_store() {
+        let source = r"
+define @f(%p) {
+    %q <- %p + 8
+    %v <- load %p
+    store %q <- 5
+    return %v
+}
+";
+        assert_eq!(num_roots(source), 2);
+    }
+
+    #[test]
+    fn load_does_not_cross_store_to_same_address() {
+        let source = r"
+define @f(%p) {
+    %v <- load %p
+    store %p <- 5
+    return %v
+}
+";
+        assert_eq!(num_roots(source), 3);
+    }
+
+    #[test]
+    fn load_does_not_cross_call() {
+        let source = r"
+define @f(%p) {
+    %v <- load %p
+    call print(5)
+    return %v
+}
+";
+        assert_eq!(num_roots(source), 2);
+    }
+
+    #[test]
+    fn arithmetic_crosses_call() {
+        let source = r"
+define @f(%p) {
+    %v <- %p + 2
+    call print(5)
+    return %v
+}
+";
+        assert_eq!(num_roots(source), 1);
+    }
+
+    #[test]
+    fn merge_across_call_redefining_operand_is_refused() {
+        let source = r"
+define @f(%p) {
+    %v <- %p + 2
+    %p <- call @g()
+    %w <- %v + %p
+    return %w
+}
+
+define @g() {
+    return 3
+}
+";
+        // %w still merges into the return
+        assert_eq!(num_roots(source), 2);
+    }
+}
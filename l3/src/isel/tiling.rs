@@ -1,9 +1,11 @@
 use std::cmp::Reverse;
+use std::mem;
 
-use l2;
 use l3::*;
+use utils::{DisplayResolved, Interner};
 
 use crate::isel::forest::{NodeKind, OpKind, SFNode, SelectionForest};
+use crate::translation::translate_value;
 
 macro_rules! pat {
     (any) => {
@@ -33,6 +35,28 @@ macro_rules! pat {
         }
     };
 
+    (reg) => {
+        Pattern {
+            children: Vec::new(),
+            matches: |node, _| {
+                matches!(
+                    &node.kind,
+                    NodeKind::Op {
+                        result: Some(_),
+                        ..
+                    } | NodeKind::Value(Value::Variable(_))
+                )
+            },
+        }
+    };
+
+    (num $num:pat $(if $guard:expr)?) => {
+        Pattern {
+            children: Vec::new(),
+            matches: |node, _| matches!(&node.kind, NodeKind::Value(Value::Number($num)) $(if $guard)?),
+        }
+    };
+
     ($kind:ident) => {
         Pattern {
             children: Vec::new(),
@@ -81,9 +105,11 @@ struct Pattern {
 
 #[derive(Debug)]
 pub struct Tile {
+    name: &'static str,
     pattern: Pattern,
     cost: u32,
     emit: fn(&SelectionForest, NodeId) -> Vec<l2::Instruction>,
+    guard: fn(&SelectionForest, NodeId) -> bool,
 }
 
 impl Tile {
@@ -93,15 +119,22 @@ impl Tile {
         emit: fn(&SelectionForest, NodeId) -> Vec<l2::Instruction>,
     ) -> Self {
         Self {
+            name: "",
             pattern,
             cost,
             emit,
+            guard: |_, _| true,
         }
     }
 
+    fn with_guard(mut self, guard: fn(&SelectionForest, NodeId) -> bool) -> Self {
+        self.guard = guard;
+        self
+    }
+
     fn size(&self) -> u32 {
         fn dfs(pat: &Pattern) -> u32 {
-            1 + pat.children.iter().map(|child| dfs(child)).sum::<u32>()
+            1 + pat.children.iter().map(dfs).sum::<u32>()
         }
         dfs(&self.pattern)
     }
@@ -127,19 +160,116 @@ impl Tile {
             unreachable!("roots should be ops");
         };
 
-        dfs(forest, root, opt, &self.pattern)
+        self.pattern.children.len() == forest.arena[root].children.len()
+            && dfs(forest, root, opt, &self.pattern)
+            && (self.guard)(forest, root)
+    }
+
+    fn uncovered(&self, forest: &SelectionForest, root: NodeId) -> Vec<NodeId> {
+        fn dfs(forest: &SelectionForest, id: NodeId, pat: &Pattern, uncovered: &mut Vec<NodeId>) {
+            if pat.children.is_empty() {
+                if matches!(forest.arena[id].kind, NodeKind::Op { .. }) {
+                    uncovered.push(id);
+                }
+            } else {
+                for (&child, p) in forest.arena[id].children.iter().zip(&pat.children) {
+                    dfs(forest, child, p, uncovered);
+                }
+            }
+        }
+
+        let mut uncovered = Vec::new();
+        for (&child, p) in forest.arena[root]
+            .children
+            .iter()
+            .zip(&self.pattern.children)
+        {
+            dfs(forest, child, p, &mut uncovered);
+        }
+        uncovered
     }
 }
 
-pub fn greedy_match(forest: &SelectionForest) {
+pub fn optimal_match(
+    forest: &SelectionForest,
+    interner: &Interner<String>,
+    verbose: bool,
+) -> Vec<Vec<l2::Instruction>> {
+    fn cover(
+        tiles: &[Tile],
+        forest: &SelectionForest,
+        id: NodeId,
+        covers: &mut [Option<(TileId, u32)>],
+    ) -> u32 {
+        if let Some((_, cost)) = covers[id] {
+            return cost;
+        }
+
+        let (tile_id, cost) = tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.matches(forest, id))
+            .map(|(i, tile)| {
+                let subtree_cost: u32 = tile
+                    .uncovered(forest, id)
+                    .into_iter()
+                    .map(|child| cover(tiles, forest, child, covers))
+                    .sum();
+                (i, tile.cost + subtree_cost)
+            })
+            .min_by_key(|&(_, cost)| cost)
+            .expect("every tree should be covered by some tile");
+
+        covers[id] = Some((tile_id, cost));
+        cost
+    }
+
+    fn emit(
+        tiles: &[Tile],
+        forest: &SelectionForest,
+        id: NodeId,
+        covers: &[Option<(TileId, u32)>],
+        chosen: &mut Vec<TileId>,
+        insts: &mut Vec<l2::Instruction>,
+    ) {
+        let (tile_id, _) = covers[id].expect("node should be covered");
+        let tile = &tiles[tile_id];
+
+        for child in tile.uncovered(forest, id) {
+            emit(tiles, forest, child, covers, chosen, insts);
+        }
+
+        chosen.push(tile_id);
+        insts.extend((tile.emit)(forest, id));
+    }
+
     let tiles = make_tiles();
+    let mut covers = vec![None; forest.arena.len()];
+    let mut selected = Vec::new();
+
     for &root in &forest.roots {
-        let match_tile = tiles
-            .iter()
-            .find(|tile| tile.matches(forest, root))
-            .unwrap();
-        dbg!((match_tile.emit)(forest, root));
+        let cost = cover(&tiles, forest, root, &mut covers);
+        let mut chosen = Vec::new();
+        let mut insts = Vec::new();
+        emit(&tiles, forest, root, &covers, &mut chosen, &mut insts);
+
+        if verbose {
+            println!(
+                "{}: {} (cost {})",
+                forest.arena[root].resolved(interner),
+                chosen
+                    .iter()
+                    .map(|&id| format!("{} {}", tiles[id].name, tiles[id].cost))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                cost
+            );
+        }
+
+        selected.push(insts);
     }
+
+    selected
 }
 
 fn translate_node(forest: &SelectionForest, id: NodeId) -> l2::Value {
@@ -150,15 +280,76 @@ fn translate_node(forest: &SelectionForest, id: NodeId) -> l2::Value {
             };
             l2::Value::Variable(l2::SymbolId(res.0))
         }
-        NodeKind::Value(val) => match val {
-            Value::Number(num) => l2::Value::Number(*num),
-            Value::Label(label) => l2::Value::Label(l2::SymbolId(label.0)),
-            Value::Function(callee) => l2::Value::Function(l2::SymbolId(callee.0)),
-            Value::Variable(var) => l2::Value::Function(l2::SymbolId(var.0)),
-        },
+        NodeKind::Value(val) => translate_value(val),
     }
 }
 
+fn fused_cjump(
+    forest: &SelectionForest,
+    root: NodeId,
+    cmp: l2::CompareOp,
+    swap: bool,
+) -> Vec<l2::Instruction> {
+    let &[cond, target] = forest.arena[root].children.as_slice() else {
+        unreachable!("branch cond node should have two children");
+    };
+    let NodeKind::Value(Value::Label(label)) = forest.arena[target].kind else {
+        unreachable!("branch cond node should have label");
+    };
+
+    let mut lhs = translate_node(forest, forest.arena[cond].children[0]);
+    let mut rhs = translate_node(forest, forest.arena[cond].children[1]);
+    if swap {
+        mem::swap(&mut lhs, &mut rhs);
+    }
+
+    vec![l2::Instruction::CJump {
+        lhs,
+        cmp,
+        rhs,
+        label: l2::SymbolId(label.0),
+    }]
+}
+
+fn translate_number(forest: &SelectionForest, id: NodeId) -> i64 {
+    let NodeKind::Value(Value::Number(num)) = forest.arena[id].kind else {
+        unreachable!("node should be a number");
+    };
+    num
+}
+
+fn lea(
+    forest: &SelectionForest,
+    root: NodeId,
+    base: NodeId,
+    index: NodeId,
+    scale: i64,
+) -> Vec<l2::Instruction> {
+    vec![l2::Instruction::LEA {
+        dst: translate_node(forest, root),
+        src: translate_node(forest, base),
+        offset: translate_node(forest, index),
+        scale: scale as u8,
+    }]
+}
+
+// Whether the load the pattern matched as operand `operand` of the stored
+// value reads from the address being stored to.
+fn same_address(forest: &SelectionForest, root: NodeId, operand: usize) -> bool {
+    let &[address, value] = forest.arena[root].children.as_slice() else {
+        unreachable!("store node should have two children");
+    };
+    let NodeKind::Value(Value::Variable(store_address)) = forest.arena[address].kind else {
+        return false;
+    };
+
+    let load = &forest.arena[forest.arena[value].children[operand]];
+    matches!(
+        forest.arena[load.children[0]].kind,
+        NodeKind::Value(Value::Variable(load_address)) if load_address == store_address
+    )
+}
+
 fn make_tiles() -> Vec<Tile> {
     use l2::Instruction as L2;
 
@@ -186,17 +377,31 @@ fn make_tiles() -> Vec<Tile> {
 
     let sub = Tile::new(pat!(Sub(pat!(any), pat!(any)) -> any), 2, |forest, root| {
         let dst = translate_node(forest, root);
-        vec![
-            L2::Assign {
-                dst,
-                src: translate_node(forest, forest.arena[root].children[0]),
-            },
-            L2::Arithmetic {
-                dst,
-                aop: l2::ArithmeticOp::SubAssign,
-                src: translate_node(forest, forest.arena[root].children[1]),
-            },
-        ]
+        let lhs = translate_node(forest, forest.arena[root].children[0]);
+        let rhs = translate_node(forest, forest.arena[root].children[1]);
+        if rhs == dst {
+            vec![
+                L2::Arithmetic {
+                    dst,
+                    aop: l2::ArithmeticOp::SubAssign,
+                    src: lhs,
+                },
+                L2::Arithmetic {
+                    dst,
+                    aop: l2::ArithmeticOp::MulAssign,
+                    src: l2::Value::Number(-1),
+                },
+            ]
+        } else {
+            vec![
+                L2::Assign { dst, src: lhs },
+                L2::Arithmetic {
+                    dst,
+                    aop: l2::ArithmeticOp::SubAssign,
+                    src: rhs,
+                },
+            ]
+        }
     });
 
     let mul = Tile::new(pat!(Mul(pat!(any), pat!(any)) -> any), 2, |forest, root| {
@@ -235,32 +440,56 @@ fn make_tiles() -> Vec<Tile> {
 
     let shl = Tile::new(pat!(Shl(pat!(any), pat!(any)) -> any), 2, |forest, root| {
         let dst = translate_node(forest, root);
-        vec![
-            L2::Assign {
-                dst,
-                src: translate_node(forest, forest.arena[root].children[0]),
-            },
-            L2::Shift {
-                dst,
-                sop: l2::ShiftOp::ShlAssign,
-                src: translate_node(forest, forest.arena[root].children[1]),
-            },
-        ]
+        let lhs = translate_node(forest, forest.arena[root].children[0]);
+        let rhs = translate_node(forest, forest.arena[root].children[1]);
+        if rhs == dst {
+            let rcx = l2::Value::Register(l2::Register::RCX);
+            vec![
+                L2::Assign { dst: rcx, src: rhs },
+                L2::Assign { dst, src: lhs },
+                L2::Shift {
+                    dst,
+                    sop: l2::ShiftOp::ShlAssign,
+                    src: rcx,
+                },
+            ]
+        } else {
+            vec![
+                L2::Assign { dst, src: lhs },
+                L2::Shift {
+                    dst,
+                    sop: l2::ShiftOp::ShlAssign,
+                    src: rhs,
+                },
+            ]
+        }
     });
 
     let shr = Tile::new(pat!(Shr(pat!(any), pat!(any)) -> any), 2, |forest, root| {
         let dst = translate_node(forest, root);
-        vec![
-            L2::Assign {
-                dst,
-                src: translate_node(forest, forest.arena[root].children[0]),
-            },
-            L2::Shift {
-                dst,
-                sop: l2::ShiftOp::ShrAssign,
-                src: translate_node(forest, forest.arena[root].children[1]),
-            },
-        ]
+        let lhs = translate_node(forest, forest.arena[root].children[0]);
+        let rhs = translate_node(forest, forest.arena[root].children[1]);
+        if rhs == dst {
+            let rcx = l2::Value::Register(l2::Register::RCX);
+            vec![
+                L2::Assign { dst: rcx, src: rhs },
+                L2::Assign { dst, src: lhs },
+                L2::Shift {
+                    dst,
+                    sop: l2::ShiftOp::ShrAssign,
+                    src: rcx,
+                },
+            ]
+        } else {
+            vec![
+                L2::Assign { dst, src: lhs },
+                L2::Shift {
+                    dst,
+                    sop: l2::ShiftOp::ShrAssign,
+                    src: rhs,
+                },
+            ]
+        }
     });
 
     let lt = Tile::new(pat!(Lt(pat!(any), pat!(any)) -> any), 1, |forest, root| {
@@ -352,13 +581,317 @@ fn make_tiles() -> Vec<Tile> {
             unreachable!("branch cond node should have label");
         };
         vec![L2::CJump {
-            lhs: translate_node(forest, forest.arena[root].children[1]),
+            lhs: translate_node(forest, forest.arena[root].children[0]),
             cmp: l2::CompareOp::Eq,
             rhs: l2::Value::Number(1),
             label: l2::SymbolId(label.0),
         }]
     });
 
+    let lt_branch = Tile::new(
+        pat!(Branch(pat!(Lt(pat!(any), pat!(any)) -> any), pat!(any))),
+        1,
+        |forest, root| fused_cjump(forest, root, l2::CompareOp::Lt, false),
+    );
+
+    let le_branch = Tile::new(
+        pat!(Branch(pat!(Le(pat!(any), pat!(any)) -> any), pat!(any))),
+        1,
+        |forest, root| fused_cjump(forest, root, l2::CompareOp::Le, false),
+    );
+
+    let eq_branch = Tile::new(
+        pat!(Branch(pat!(Eq(pat!(any), pat!(any)) -> any), pat!(any))),
+        1,
+        |forest, root| fused_cjump(forest, root, l2::CompareOp::Eq, false),
+    );
+
+    let ge_branch = Tile::new(
+        pat!(Branch(pat!(Ge(pat!(any), pat!(any)) -> any), pat!(any))),
+        1,
+        |forest, root| fused_cjump(forest, root, l2::CompareOp::Le, true),
+    );
+
+    let gt_branch = Tile::new(
+        pat!(Branch(pat!(Gt(pat!(any), pat!(any)) -> any), pat!(any))),
+        1,
+        |forest, root| fused_cjump(forest, root, l2::CompareOp::Lt, true),
+    );
+
+    let lea_shl_right = Tile::new(
+        pat!(Add(pat!(reg), pat!(Shl(pat!(reg), pat!(num 1..=3)) -> any)) -> any),
+        1,
+        |forest, root| {
+            let &[base, scaled] = forest.arena[root].children.as_slice() else {
+                unreachable!("add node should have two children");
+            };
+            let &[index, amount] = forest.arena[scaled].children.as_slice() else {
+                unreachable!("shl node should have two children");
+            };
+            lea(
+                forest,
+                root,
+                base,
+                index,
+                1 << translate_number(forest, amount),
+            )
+        },
+    );
+
+    let lea_shl_left = Tile::new(
+        pat!(Add(pat!(Shl(pat!(reg), pat!(num 1..=3)) -> any), pat!(reg)) -> any),
+        1,
+        |forest, root| {
+            let &[scaled, base] = forest.arena[root].children.as_slice() else {
+                unreachable!("add node should have two children");
+            };
+            let &[index, amount] = forest.arena[scaled].children.as_slice() else {
+                unreachable!("shl node should have two children");
+            };
+            lea(
+                forest,
+                root,
+                base,
+                index,
+                1 << translate_number(forest, amount),
+            )
+        },
+    );
+
+    let lea_mul_right = Tile::new(
+        pat!(Add(pat!(reg), pat!(Mul(pat!(reg), pat!(num 2 | 4 | 8)) -> any)) -> any),
+        1,
+        |forest, root| {
+            let &[base, scaled] = forest.arena[root].children.as_slice() else {
+                unreachable!("add node should have two children");
+            };
+            let &[index, factor] = forest.arena[scaled].children.as_slice() else {
+                unreachable!("mul node should have two children");
+            };
+            lea(forest, root, base, index, translate_number(forest, factor))
+        },
+    );
+
+    let lea_mul_left = Tile::new(
+        pat!(Add(pat!(Mul(pat!(reg), pat!(num 2 | 4 | 8)) -> any), pat!(reg)) -> any),
+        1,
+        |forest, root| {
+            let &[scaled, base] = forest.arena[root].children.as_slice() else {
+                unreachable!("add node should have two children");
+            };
+            let &[index, factor] = forest.arena[scaled].children.as_slice() else {
+                unreachable!("mul node should have two children");
+            };
+            lea(forest, root, base, index, translate_number(forest, factor))
+        },
+    );
+
+    let lea_mul_right_swapped = Tile::new(
+        pat!(Add(pat!(reg), pat!(Mul(pat!(num 2 | 4 | 8), pat!(reg)) -> any)) -> any),
+        1,
+        |forest, root| {
+            let &[base, scaled] = forest.arena[root].children.as_slice() else {
+                unreachable!("add node should have two children");
+            };
+            let &[factor, index] = forest.arena[scaled].children.as_slice() else {
+                unreachable!("mul node should have two children");
+            };
+            lea(forest, root, base, index, translate_number(forest, factor))
+        },
+    );
+
+    let lea_mul_left_swapped = Tile::new(
+        pat!(Add(pat!(Mul(pat!(num 2 | 4 | 8), pat!(reg)) -> any), pat!(reg)) -> any),
+        1,
+        |forest, root| {
+            let &[scaled, base] = forest.arena[root].children.as_slice() else {
+                unreachable!("add node should have two children");
+            };
+            let &[factor, index] = forest.arena[scaled].children.as_slice() else {
+                unreachable!("mul node should have two children");
+            };
+            lea(forest, root, base, index, translate_number(forest, factor))
+        },
+    );
+
+    let load_offset_right = Tile::new(
+        pat!(Load(pat!(Add(pat!(reg), pat!(num n if n % 8 == 0)) -> any)) -> any),
+        1,
+        |forest, root| {
+            let address = &forest.arena[forest.arena[root].children[0]];
+            vec![L2::Load {
+                dst: translate_node(forest, root),
+                src: translate_node(forest, address.children[0]),
+                offset: translate_number(forest, address.children[1]),
+            }]
+        },
+    );
+
+    let load_offset_left = Tile::new(
+        pat!(Load(pat!(Add(pat!(num n if n % 8 == 0), pat!(reg)) -> any)) -> any),
+        1,
+        |forest, root| {
+            let address = &forest.arena[forest.arena[root].children[0]];
+            vec![L2::Load {
+                dst: translate_node(forest, root),
+                src: translate_node(forest, address.children[1]),
+                offset: translate_number(forest, address.children[0]),
+            }]
+        },
+    );
+
+    let store_offset_right = Tile::new(
+        pat!(Store(
+            pat!(Add(pat!(reg), pat!(num n if n % 8 == 0)) -> any),
+            pat!(any)
+        )),
+        1,
+        |forest, root| {
+            let address = &forest.arena[forest.arena[root].children[0]];
+            vec![L2::Store {
+                dst: translate_node(forest, address.children[0]),
+                offset: translate_number(forest, address.children[1]),
+                src: translate_node(forest, forest.arena[root].children[1]),
+            }]
+        },
+    );
+
+    let store_offset_left = Tile::new(
+        pat!(Store(
+            pat!(Add(pat!(num n if n % 8 == 0), pat!(reg)) -> any),
+            pat!(any)
+        )),
+        1,
+        |forest, root| {
+            let address = &forest.arena[forest.arena[root].children[0]];
+            vec![L2::Store {
+                dst: translate_node(forest, address.children[1]),
+                offset: translate_number(forest, address.children[0]),
+                src: translate_node(forest, forest.arena[root].children[1]),
+            }]
+        },
+    );
+
+    let increment_left = Tile::new(
+        pat!(Add(pat!(var), pat!(num 1)) -> var),
+        1,
+        |forest, root| vec![L2::Increment(translate_node(forest, root))],
+    );
+
+    let increment_right = Tile::new(
+        pat!(Add(pat!(num 1), pat!(var)) -> var),
+        1,
+        |forest, root| vec![L2::Increment(translate_node(forest, root))],
+    );
+
+    let decrement = Tile::new(
+        pat!(Sub(pat!(var), pat!(num 1)) -> var),
+        1,
+        |forest, root| vec![L2::Decrement(translate_node(forest, root))],
+    );
+
+    let store_add_left = Tile::new(
+        pat!(Store(
+            pat!(reg),
+            pat!(Add(pat!(Load(pat!(reg)) -> any), pat!(any)) -> any)
+        )),
+        1,
+        |forest, root| {
+            let &[address, value] = forest.arena[root].children.as_slice() else {
+                unreachable!("store node should have two children");
+            };
+            vec![L2::StoreArithmetic {
+                dst: translate_node(forest, address),
+                offset: 0,
+                aop: l2::ArithmeticOp::AddAssign,
+                src: translate_node(forest, forest.arena[value].children[1]),
+            }]
+        },
+    )
+    .with_guard(|forest, root| same_address(forest, root, 0));
+
+    let store_add_right = Tile::new(
+        pat!(Store(
+            pat!(reg),
+            pat!(Add(pat!(any), pat!(Load(pat!(reg)) -> any)) -> any)
+        )),
+        1,
+        |forest, root| {
+            let &[address, value] = forest.arena[root].children.as_slice() else {
+                unreachable!("store node should have two children");
+            };
+            vec![L2::StoreArithmetic {
+                dst: translate_node(forest, address),
+                offset: 0,
+                aop: l2::ArithmeticOp::AddAssign,
+                src: translate_node(forest, forest.arena[value].children[0]),
+            }]
+        },
+    )
+    .with_guard(|forest, root| same_address(forest, root, 1));
+
+    let store_sub = Tile::new(
+        pat!(Store(
+            pat!(reg),
+            pat!(Sub(pat!(Load(pat!(reg)) -> any), pat!(any)) -> any)
+        )),
+        1,
+        |forest, root| {
+            let &[address, value] = forest.arena[root].children.as_slice() else {
+                unreachable!("store node should have two children");
+            };
+            vec![L2::StoreArithmetic {
+                dst: translate_node(forest, address),
+                offset: 0,
+                aop: l2::ArithmeticOp::SubAssign,
+                src: translate_node(forest, forest.arena[value].children[1]),
+            }]
+        },
+    )
+    .with_guard(|forest, root| same_address(forest, root, 0));
+
+    let load_add_left = Tile::new(
+        pat!(Add(pat!(var), pat!(Load(pat!(reg)) -> any)) -> var),
+        1,
+        |forest, root| {
+            let load = &forest.arena[forest.arena[root].children[1]];
+            vec![L2::LoadArithmetic {
+                dst: translate_node(forest, root),
+                aop: l2::ArithmeticOp::AddAssign,
+                src: translate_node(forest, load.children[0]),
+                offset: 0,
+            }]
+        },
+    );
+
+    let load_add_right = Tile::new(
+        pat!(Add(pat!(Load(pat!(reg)) -> any), pat!(var)) -> var),
+        1,
+        |forest, root| {
+            let load = &forest.arena[forest.arena[root].children[0]];
+            vec![L2::LoadArithmetic {
+                dst: translate_node(forest, root),
+                aop: l2::ArithmeticOp::AddAssign,
+                src: translate_node(forest, load.children[0]),
+                offset: 0,
+            }]
+        },
+    );
+
+    let load_sub = Tile::new(
+        pat!(Sub(pat!(var), pat!(Load(pat!(reg)) -> any)) -> var),
+        1,
+        |forest, root| {
+            let load = &forest.arena[forest.arena[root].children[1]];
+            vec![L2::LoadArithmetic {
+                dst: translate_node(forest, root),
+                aop: l2::ArithmeticOp::SubAssign,
+                src: translate_node(forest, load.children[0]),
+                offset: 0,
+            }]
+        },
+    );
+
     let add_assign_left = Tile::new(pat!(Add(pat!(var), pat!(any)) -> var), 1, |forest, root| {
         vec![L2::Arithmetic {
             dst: translate_node(forest, root),
@@ -383,14 +916,6 @@ fn make_tiles() -> Vec<Tile> {
         }]
     });
 
-    let sub_assign_right = Tile::new(pat!(Sub(pat!(any), pat!(var)) -> var), 1, |forest, root| {
-        vec![L2::Arithmetic {
-            dst: translate_node(forest, root),
-            aop: l2::ArithmeticOp::SubAssign,
-            src: translate_node(forest, forest.arena[root].children[0]),
-        }]
-    });
-
     let mul_assign_left = Tile::new(pat!(Mul(pat!(var), pat!(any)) -> var), 1, |forest, root| {
         vec![L2::Arithmetic {
             dst: translate_node(forest, root),
@@ -431,7 +956,17 @@ fn make_tiles() -> Vec<Tile> {
         },
     );
 
-    let mut tiles = vec![
+    // every tile is named after the variable holding it
+    macro_rules! named {
+        ($($tile:ident),* $(,)?) => {
+            vec![$(Tile {
+                name: stringify!($tile).trim_end_matches('_'),
+                ..$tile
+            }),*]
+        };
+    }
+
+    let mut tiles = named![
         assign,
         add,
         sub,
@@ -450,10 +985,33 @@ fn make_tiles() -> Vec<Tile> {
         return_value,
         branch,
         branch_cond,
+        lt_branch,
+        le_branch,
+        eq_branch,
+        ge_branch,
+        gt_branch,
+        lea_shl_right,
+        lea_shl_left,
+        lea_mul_right,
+        lea_mul_left,
+        lea_mul_right_swapped,
+        lea_mul_left_swapped,
+        load_offset_right,
+        load_offset_left,
+        store_offset_right,
+        store_offset_left,
+        increment_left,
+        increment_right,
+        decrement,
+        store_add_left,
+        store_add_right,
+        store_sub,
+        load_add_left,
+        load_add_right,
+        load_sub,
         add_assign_left,
         add_assign_right,
         sub_assign_left,
-        sub_assign_right,
         mul_assign_left,
         mul_assign_right,
         bit_and_assign_left,
@@ -462,3 +1020,66 @@ fn make_tiles() -> Vec<Tile> {
     tiles.sort_by_key(|tile| (Reverse(tile.size()), tile.cost));
     tiles
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{build_def_use, compute_liveness, compute_reaching_def};
+    use crate::isel::{create_contexts, generate_forests};
+    use crate::testing::{parse, symbol};
+
+    fn selected(prog: &Program) -> Vec<l2::Instruction> {
+        let func = &prog.functions[0];
+        let liveness = compute_liveness(func);
+        let reaching_def = compute_reaching_def(func);
+        let def_use = build_def_use(func, &reaching_def);
+        let mut contexts = create_contexts(func);
+
+        generate_forests(func, &liveness, &def_use, &mut contexts)
+            .iter()
+            .flat_map(|forest| optimal_match(forest, &prog.interner, false))
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn updates_memory_in_place_only_from_the_stored_address() {
+        for sum in ["%a + %b", "%b + %a"] {
+            let prog = parse(&format!(
+                r"
+define @f(%p, %q) {{
+    %a <- load %q
+    %b <- load %p
+    %s <- {}
+    store %p <- %s
+    return
+}}
+",
+                sum
+            ));
+            let [p, q] =
+                ["p", "q"].map(|name| l2::Value::Variable(l2::SymbolId(symbol(&prog, name).0)));
+            let insts = selected(&prog);
+
+            let loaded = insts
+                .iter()
+                .find_map(|inst| match inst {
+                    l2::Instruction::Load { dst, src, .. } if *src == q => Some(*dst),
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("%q should be loaded: {:?}", insts));
+            let updated = insts.iter().any(|inst| {
+                matches!(
+                    inst,
+                    l2::Instruction::StoreArithmetic {
+                        dst,
+                        aop: l2::ArithmeticOp::AddAssign,
+                        src,
+                        ..
+                    } if *dst == p && *src == loaded
+                )
+            });
+            assert!(updated, "{:?}", insts);
+        }
+    }
+}
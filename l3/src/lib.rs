@@ -3,7 +3,7 @@ use std::fmt;
 use std::hash::Hash;
 use std::iter;
 
-use utils::{DisplayResolved, Interner};
+use utils::{BlockIndex, ControlFlow, DisplayResolved, Interner};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Callee {
@@ -139,6 +139,14 @@ pub enum Instruction {
         callee: Callee,
         args: Vec<Value>,
     },
+    TailCall {
+        callee: Callee,
+        args: Vec<Value>,
+    },
+    Phi {
+        dst: SymbolId,
+        args: Vec<(BlockId, Value)>,
+    },
 }
 
 impl Instruction {
@@ -150,7 +158,8 @@ impl Instruction {
             | Binary { dst, .. }
             | Compare { dst, .. }
             | Load { dst, .. }
-            | CallResult { dst, .. } => Some(*dst),
+            | CallResult { dst, .. }
+            | Phi { dst, .. } => Some(*dst),
 
             Store { .. }
             | Return
@@ -158,7 +167,8 @@ impl Instruction {
             | Label(_)
             | Branch(_)
             | BranchCond { .. }
-            | Call { .. } => None,
+            | Call { .. }
+            | TailCall { .. } => None,
         }
     }
 
@@ -213,23 +223,135 @@ impl Instruction {
                 }
             }
 
-            Call { callee, args } | CallResult { callee, args, .. } => args
+            Call { callee, args } | CallResult { callee, args, .. } | TailCall { callee, args } => {
+                args.iter()
+                    .filter_map(|arg| {
+                        if let Value::Variable(id) = arg {
+                            Some(*id)
+                        } else {
+                            None
+                        }
+                    })
+                    .chain(if let Callee::Value(Value::Variable(id)) = callee {
+                        Some(*id)
+                    } else {
+                        None
+                    })
+                    .collect()
+            }
+
+            Phi { args, .. } => args
                 .iter()
-                .filter_map(|arg| {
+                .filter_map(|(_, arg)| {
                     if let Value::Variable(id) = arg {
                         Some(*id)
                     } else {
                         None
                     }
                 })
-                .chain(if let Callee::Value(Value::Variable(id)) = callee {
-                    Some(*id)
-                } else {
-                    None
-                })
                 .collect(),
         }
     }
+
+    pub fn defs_mut(&mut self) -> Option<&mut SymbolId> {
+        use Instruction::*;
+
+        match self {
+            Assign { dst, .. }
+            | Binary { dst, .. }
+            | Compare { dst, .. }
+            | Load { dst, .. }
+            | CallResult { dst, .. }
+            | Phi { dst, .. } => Some(dst),
+
+            Store { .. }
+            | Return
+            | ReturnValue(_)
+            | Label(_)
+            | Branch(_)
+            | BranchCond { .. }
+            | Call { .. }
+            | TailCall { .. } => None,
+        }
+    }
+
+    pub fn uses_mut(&mut self) -> Vec<&mut SymbolId> {
+        use Instruction::*;
+
+        fn variable(val: &mut Value) -> Option<&mut SymbolId> {
+            if let Value::Variable(id) = val {
+                Some(id)
+            } else {
+                None
+            }
+        }
+
+        match self {
+            Assign { src, .. } => variable(src).into_iter().collect(),
+
+            Binary { lhs, rhs, .. } | Compare { lhs, rhs, .. } => {
+                variable(lhs).into_iter().chain(variable(rhs)).collect()
+            }
+
+            Load { src, .. } => vec![src],
+
+            Store { dst, src } => iter::once(dst).chain(variable(src)).collect(),
+
+            Return | Label(_) | Branch(_) => Vec::new(),
+
+            ReturnValue(val) => variable(val).into_iter().collect(),
+
+            BranchCond { cond, .. } => variable(cond).into_iter().collect(),
+
+            Call { callee, args } | CallResult { callee, args, .. } | TailCall { callee, args } => {
+                args.iter_mut()
+                    .filter_map(variable)
+                    .chain(if let Callee::Value(val) = callee {
+                        variable(val)
+                    } else {
+                        None
+                    })
+                    .collect()
+            }
+
+            Phi { args, .. } => args
+                .iter_mut()
+                .filter_map(|(_, arg)| variable(arg))
+                .collect(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Value> {
+        use Instruction::*;
+
+        match self {
+            Assign { src, .. } | Store { src, .. } => vec![src],
+            Binary { lhs, rhs, .. } | Compare { lhs, rhs, .. } => vec![lhs, rhs],
+            ReturnValue(val) => vec![val],
+            BranchCond { cond, .. } => vec![cond],
+            Call { args, .. } | CallResult { args, .. } | TailCall { args, .. } => {
+                args.iter().collect()
+            }
+            Phi { args, .. } => args.iter().map(|(_, arg)| arg).collect(),
+            Load { .. } | Return | Label(_) | Branch(_) => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        use Instruction::*;
+
+        match self {
+            Assign { src, .. } | Store { src, .. } => vec![src],
+            Binary { lhs, rhs, .. } | Compare { lhs, rhs, .. } => vec![lhs, rhs],
+            ReturnValue(val) => vec![val],
+            BranchCond { cond, .. } => vec![cond],
+            Call { args, .. } | CallResult { args, .. } | TailCall { args, .. } => {
+                args.iter_mut().collect()
+            }
+            Phi { args, .. } => args.iter_mut().map(|(_, arg)| arg).collect(),
+            Load { .. } | Return | Label(_) | Branch(_) => Vec::new(),
+        }
+    }
 }
 
 impl DisplayResolved for Instruction {
@@ -290,6 +412,15 @@ impl DisplayResolved for Instruction {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
+            TailCall { callee, args } => write!(
+                f,
+                "tail-call {}({})",
+                callee.resolved(interner),
+                args.iter()
+                    .map(|arg| format!("{}", arg.resolved(interner)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
             CallResult { dst, callee, args } => write!(
                 f,
                 "%{} <- call {}({})",
@@ -300,6 +431,15 @@ impl DisplayResolved for Instruction {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
+            Phi { dst, args } => write!(
+                f,
+                "%{} <- phi({})",
+                interner.resolve(dst.0),
+                args.iter()
+                    .map(|(pred, arg)| format!("{}: {}", pred.0, arg.resolved(interner)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
         }
     }
 }
@@ -322,6 +462,16 @@ impl DisplayResolved for BasicBlock {
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub struct BlockId(pub usize);
 
+impl BlockIndex for BlockId {
+    fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    fn index(self) -> usize {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: SymbolId,
@@ -343,6 +493,7 @@ impl Function {
             match inst {
                 Instruction::Return
                 | Instruction::ReturnValue(_)
+                | Instruction::TailCall { .. }
                 | Instruction::Branch(_)
                 | Instruction::BranchCond { .. } => {
                     block.instructions.push(inst);
@@ -369,7 +520,7 @@ impl Function {
 
         if basic_blocks
             .last()
-            .map_or(false, |block| block.instructions.is_empty())
+            .is_some_and(|block| block.instructions.is_empty())
         {
             basic_blocks.pop();
         }
@@ -412,6 +563,22 @@ pub struct ControlFlowGraph {
     pub successors: Vec<Vec<BlockId>>,
 }
 
+impl ControlFlow for ControlFlowGraph {
+    type Block = BlockId;
+
+    fn num_blocks(&self) -> usize {
+        self.successors.len()
+    }
+
+    fn successors(&self, block: BlockId) -> &[BlockId] {
+        &self.successors[block.0]
+    }
+
+    fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[block.0]
+    }
+}
+
 impl ControlFlowGraph {
     pub fn new(basic_blocks: &[BasicBlock]) -> Self {
         let id_map: HashMap<SymbolId, BlockId> = basic_blocks
@@ -450,7 +617,9 @@ impl ControlFlowGraph {
                     cfg.predecessors[succ.0].push(block.id);
                 }
 
-                Some(Instruction::Return) | Some(Instruction::ReturnValue(_)) => (),
+                Some(Instruction::Return)
+                | Some(Instruction::ReturnValue(_))
+                | Some(Instruction::TailCall { .. }) => (),
 
                 Some(_) => {
                     if i < last_index {
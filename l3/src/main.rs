@@ -1,13 +1,28 @@
 mod analysis;
+mod codegen;
+mod globalization;
 mod isel;
+mod optimization;
 mod parser;
+mod ssa;
+#[cfg(test)]
+mod testing;
+mod translation;
 
 use clap::Parser;
 use utils::DisplayResolved;
 
-use crate::analysis::{build_def_use, compute_liveness, compute_reaching_def};
-use crate::isel::{create_contexts, generate_forests, greedy_match};
+use crate::analysis::{build_call_graph, compute_available_exprs, compute_very_busy_exprs};
+use crate::codegen::generate_code;
+use crate::globalization::globalize_labels;
+use crate::optimization::{
+    eliminate_dead_code, eliminate_dead_code_aggressively, eliminate_partial_redundancies,
+    fold_constants, hoist_loop_invariants, inline_functions, mark_tail_calls,
+    number_values_globally, number_values_locally, propagate_constants, propagate_copies,
+    reduce_strength, remove_unreachable_functions, simplify_cfg,
+};
 use crate::parser::parse_file;
+use crate::ssa::{construct_ssa, destruct_ssa};
 
 #[derive(Parser)]
 struct Cli {
@@ -17,26 +32,79 @@ struct Cli {
     #[arg(short, default_value_t = 1)]
     generate: u8,
 
+    #[arg(short, default_value_t = false)]
+    call_graph: bool,
+
+    #[arg(short, default_value_t = false)]
+    expressions: bool,
+
+    #[arg(short = 'O', default_value_t = 1)]
+    optimize: u8,
+
     source: String,
 }
 
 fn main() {
     let cli = Cli::parse();
-    if let Some(prog) = parse_file(&cli.source) {
+    if let Some(mut prog) = parse_file(&cli.source) {
         if cli.verbose {
             print!("{}", &prog);
         }
 
-        for func in &prog.functions {
-            let liveness = compute_liveness(func);
-            let reaching_def = compute_reaching_def(func);
-            let def_use = build_def_use(func, &reaching_def);
-            let mut contexts = create_contexts(func);
-            let forests = generate_forests(func, &liveness, &def_use, &mut contexts);
-            for forest in &forests {
-                // print!("{}", forest.resolved(&prog.interner));
-                greedy_match(forest);
+        if cli.optimize > 0 {
+            inline_functions(&mut prog);
+            remove_unreachable_functions(&mut prog);
+        }
+
+        if cli.call_graph {
+            print!("{}", build_call_graph(&prog).resolved(&prog.interner));
+        }
+
+        if cli.optimize > 0 {
+            for func in &mut prog.functions {
+                fold_constants(func);
+                simplify_cfg(func);
+                construct_ssa(func, &mut prog.interner);
+                propagate_constants(func, &mut prog.interner);
+                number_values_globally(func);
+                hoist_loop_invariants(func, &mut prog.interner);
+                reduce_strength(func, &mut prog.interner);
+            }
+
+            if cli.verbose {
+                print!("{}", &prog);
             }
         }
+
+        for func in &mut prog.functions {
+            if cli.optimize > 0 {
+                destruct_ssa(func, &mut prog.interner);
+            }
+
+            if cli.expressions {
+                let name = prog.interner.resolve(func.name.0);
+                println!("@{} available", name);
+                print!("{}", compute_available_exprs(func).resolved(&prog.interner));
+                println!("@{} very busy", name);
+                print!("{}", compute_very_busy_exprs(func).resolved(&prog.interner));
+            }
+
+            if cli.optimize > 0 {
+                eliminate_partial_redundancies(func, &mut prog.interner);
+                propagate_copies(func);
+                fold_constants(func);
+                number_values_locally(func);
+                eliminate_dead_code_aggressively(func);
+                eliminate_dead_code(func);
+                simplify_cfg(func);
+                mark_tail_calls(func);
+            }
+        }
+
+        globalize_labels(&mut prog);
+
+        if cli.generate == 1 {
+            generate_code(&prog, cli.verbose).unwrap();
+        }
     }
 }
@@ -0,0 +1,28 @@
+mod dce;
+mod folding;
+mod inlining;
+mod licm;
+mod pre;
+mod propagation;
+mod sccp;
+mod simplification;
+mod strength_reduction;
+mod tail_calls;
+mod value_numbering;
+
+pub use crate::optimization::dce::{
+    eliminate_dead_code, eliminate_dead_code_aggressively, remove_unreachable_blocks,
+    remove_unreachable_functions,
+};
+pub use crate::optimization::folding::fold_constants;
+#[cfg(test)]
+pub use crate::optimization::folding::{evaluate_binary, evaluate_compare};
+pub use crate::optimization::inlining::inline_functions;
+pub use crate::optimization::licm::hoist_loop_invariants;
+pub use crate::optimization::pre::eliminate_partial_redundancies;
+pub use crate::optimization::propagation::propagate_copies;
+pub use crate::optimization::sccp::propagate_constants;
+pub use crate::optimization::simplification::simplify_cfg;
+pub use crate::optimization::strength_reduction::reduce_strength;
+pub use crate::optimization::tail_calls::mark_tail_calls;
+pub use crate::optimization::value_numbering::{number_values_globally, number_values_locally};
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::{BitVector, DominatorTree, Worklist};
+
+use crate::analysis::{build_call_graph, compute_liveness};
+use crate::optimization::simplification::referenced_labels;
+
+pub fn remove_unreachable_blocks(func: &mut Function) {
+    let mut reachable = BitVector::new(func.basic_blocks.len());
+    let mut stack = vec![BlockId(0)];
+
+    while let Some(id) = stack.pop() {
+        if !reachable.test(id.0) {
+            reachable.set(id.0);
+            stack.extend(func.cfg.successors[id.0].iter().copied());
+        }
+    }
+
+    // a label taken as a value must still be defined
+    let referenced = referenced_labels(func);
+    func.basic_blocks.retain(|block| {
+        reachable.test(block.id.0)
+            || match block.instructions.first() {
+                Some(Instruction::Label(label)) => referenced.contains(label),
+                _ => false,
+            }
+    });
+
+    renumber_blocks(func);
+}
+
+// Gives the remaining blocks consecutive ids and drops the phi arguments of
+// removed predecessors.
+fn renumber_blocks(func: &mut Function) {
+    let mut renumbered = HashMap::new();
+    for (i, block) in func.basic_blocks.iter_mut().enumerate() {
+        renumbered.insert(block.id, BlockId(i));
+        block.id = BlockId(i);
+    }
+
+    for inst in func
+        .basic_blocks
+        .iter_mut()
+        .flat_map(|block| &mut block.instructions)
+    {
+        if let Instruction::Phi { args, .. } = inst {
+            args.retain(|(pred, _)| renumbered.contains_key(pred));
+            for (pred, _) in args {
+                *pred = renumbered[pred];
+            }
+        }
+    }
+
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+}
+
+pub fn remove_unreachable_functions(prog: &mut Program) {
+    let call_graph = build_call_graph(prog);
+    let Some(entry) = prog
+        .interner
+        .get(&"main".to_string())
+        .and_then(|main| call_graph.index_of(main))
+    else {
+        return;
+    };
+
+    let reachable = call_graph.reachable_from(entry);
+    let mut index = 0..;
+    prog.functions
+        .retain(|_| reachable.test(index.next().unwrap()));
+}
+
+pub fn eliminate_dead_code(func: &mut Function) {
+    loop {
+        let liveness = compute_liveness(func);
+        let mut changed = false;
+
+        for block in &mut func.basic_blocks {
+            let mut dead: Vec<bool> = block
+                .instructions
+                .iter()
+                .enumerate()
+                .map(|(i, inst)| {
+                    is_pure(inst)
+                        && inst
+                            .defs()
+                            .is_some_and(|def| liveness.is_dead_at(block.id, i, def))
+                })
+                .collect();
+
+            if dead.iter().all(|&dead| dead) {
+                dead.pop();
+            }
+
+            changed |= dead.contains(&true);
+
+            let mut dead = dead.into_iter();
+            block.instructions.retain(|_| !dead.next().unwrap_or(false));
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+pub fn eliminate_dead_code_aggressively(func: &mut Function) {
+    let post_dom = DominatorTree::post_dominators(&func.cfg);
+
+    let mut def_sites: HashMap<SymbolId, Vec<(BlockId, usize)>> = HashMap::new();
+    for block in &func.basic_blocks {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            if let Some(def) = inst.defs() {
+                def_sites.entry(def).or_default().push((block.id, i));
+            }
+        }
+    }
+
+    let mut marked: Vec<BitVector> = func
+        .basic_blocks
+        .iter()
+        .map(|block| BitVector::new(block.instructions.len()))
+        .collect();
+    let mut worklist = Worklist::new();
+
+    for block in &func.basic_blocks {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            if is_root(func, &post_dom, block.id, inst) {
+                worklist.push((block.id, i));
+            }
+        }
+    }
+
+    let terminator = |id: BlockId| {
+        let block = &func.basic_blocks[id.0];
+        matches!(
+            block.instructions.last(),
+            Some(Instruction::BranchCond { .. })
+        )
+        .then(|| (id, block.instructions.len() - 1))
+    };
+
+    while let Some((b, i)) = worklist.pop() {
+        if marked[b.0].test(i) {
+            continue;
+        }
+        marked[b.0].set(i);
+
+        let inst = &func.basic_blocks[b.0].instructions[i];
+
+        for use_ in inst.uses() {
+            worklist.extend(def_sites.get(&use_).into_iter().flatten().copied());
+        }
+
+        worklist.extend(post_dom.frontier(b).iter().filter_map(|&id| terminator(id)));
+
+        if let Instruction::Phi { args, .. } = inst {
+            for &(pred, _) in args {
+                worklist.extend(terminator(pred));
+                worklist.extend(
+                    post_dom
+                        .frontier(pred)
+                        .iter()
+                        .filter_map(|&id| terminator(id)),
+                );
+            }
+        }
+    }
+
+    let labels: Vec<Option<SymbolId>> = func
+        .basic_blocks
+        .iter()
+        .map(|block| match block.instructions.first() {
+            Some(Instruction::Label(label)) => Some(*label),
+            _ => None,
+        })
+        .collect();
+
+    for block in &mut func.basic_blocks {
+        let id = block.id;
+        let mut i = 0;
+
+        block.instructions.retain_mut(|inst| {
+            let live = marked[id.0].test(i);
+            i += 1;
+
+            match inst {
+                _ if live => true,
+
+                Instruction::Label(_) | Instruction::Branch(_) => true,
+
+                Instruction::BranchCond { .. } => {
+                    let target = post_dom
+                        .idom(id)
+                        .expect("unmarked branch should have a post-dominator");
+
+                    if target.0 == id.0 + 1 {
+                        false
+                    } else {
+                        let label = labels[target.0].expect("post-dominator should have a label");
+                        *inst = Instruction::Branch(label);
+                        true
+                    }
+                }
+
+                _ => false,
+            }
+        });
+    }
+
+    // Labels and jumps are never swept, so a block left empty had neither and
+    // was only entered by falling through from the block before it; any
+    // conditional branch it ended in was dropped for falling through as well.
+    // It becomes a jump to the next block, or disappears when the next block
+    // is unlabeled and thus entered through it alone.
+    for i in 0..func.basic_blocks.len() {
+        if func.basic_blocks[i].instructions.is_empty()
+            && let Some(&Some(next)) = labels.get(i + 1)
+        {
+            func.basic_blocks[i]
+                .instructions
+                .push(Instruction::Branch(next));
+        }
+    }
+
+    func.basic_blocks
+        .retain(|block| !block.instructions.is_empty());
+    renumber_blocks(func);
+    remove_unreachable_blocks(func);
+}
+
+fn is_pure(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Assign { .. }
+            | Instruction::Binary { .. }
+            | Instruction::Compare { .. }
+            | Instruction::Load { .. }
+            | Instruction::Phi { .. }
+    )
+}
+
+fn is_root(
+    func: &Function,
+    post_dom: &DominatorTree<BlockId>,
+    id: BlockId,
+    inst: &Instruction,
+) -> bool {
+    match inst {
+        Instruction::Store { .. }
+        | Instruction::Call { .. }
+        | Instruction::CallResult { .. }
+        | Instruction::TailCall { .. }
+        | Instruction::Return
+        | Instruction::ReturnValue(_) => true,
+
+        Instruction::BranchCond { .. } => {
+            let Some(target) = post_dom.idom(id) else {
+                return true;
+            };
+            let target_block = &func.basic_blocks[target.0];
+
+            func.cfg.successors[id.0]
+                .iter()
+                .any(|&succ| !post_dom.is_reachable(succ))
+                || target_block
+                    .instructions
+                    .iter()
+                    .any(|inst| matches!(inst, Instruction::Phi { .. }))
+                || (target.0 != id.0 + 1
+                    && !matches!(
+                        target_block.instructions.first(),
+                        Some(Instruction::Label(_))
+                    ))
+        }
+
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{count, transform};
+
+    fn computes(inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Binary { .. } | Instruction::Compare { .. }
+        )
+    }
+
+    #[test]
+    fn removes_dead_definitions() {
+        let prog = transform(
+            r"
+define @main() {
+    %a <- call input()
+    %p <- call allocate(3, 1)
+    %q <- %p + 8
+    %b <- %a + 2
+    %c <- %b * 4
+    %d <- load %q
+    store %q <- %b
+    call print(%p)
+    return
+}
+",
+            &[&[4]],
+            |func, _| eliminate_dead_code(func),
+        );
+        let dead = |inst: &Instruction| {
+            matches!(
+                inst,
+                Instruction::Load { .. }
+                    | Instruction::Binary {
+                        op: BinaryOp::Mul,
+                        ..
+                    }
+            )
+        };
+        assert_eq!(count(&prog, dead), 0, "\n{}", prog);
+        let stores = |inst: &Instruction| matches!(inst, Instruction::Store { .. });
+        assert_eq!(count(&prog, stores), 1, "\n{}", prog);
+    }
+
+    #[test]
+    fn removes_dead_fall_through_block() {
+        let prog = transform(
+            r"
+define @main() {
+    %a <- call input()
+    %c <- %a < 5
+    br %c :join
+    %x <- %a + 2
+    :join
+    call print(%a)
+    return
+}
+",
+            &[&[1], &[4]],
+            |func, _| eliminate_dead_code_aggressively(func),
+        );
+        assert_eq!(count(&prog, computes), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn removes_loop_without_effects() {
+        let prog = transform(
+            r"
+define @main() {
+    %n <- call input()
+    %i <- 0
+    :loop
+    %c <- %i < %n
+    br %c :body
+    call print(1)
+    return
+    :body
+    %i <- %i + 2
+    br :loop
+}
+",
+            &[&[0], &[3]],
+            |func, _| eliminate_dead_code_aggressively(func),
+        );
+        assert_eq!(count(&prog, computes), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn keeps_branch_controlling_effects() {
+        transform(
+            r"
+define @main() {
+    %a <- call input()
+    %c <- %a < 5
+    br %c :skip
+    call print(%a)
+    :skip
+    return
+}
+",
+            &[&[1], &[4]],
+            |func, _| eliminate_dead_code_aggressively(func),
+        );
+    }
+}
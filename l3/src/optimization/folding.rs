@@ -0,0 +1,208 @@
+use l3::*;
+
+pub fn fold_constants(func: &mut Function) {
+    for block in &mut func.basic_blocks {
+        for inst in &mut block.instructions {
+            if let Some(folded) = simplify(inst) {
+                *inst = folded;
+            }
+        }
+
+        block.instructions.retain(|inst| {
+            !matches!(
+                inst,
+                Instruction::BranchCond {
+                    cond: Value::Number(_),
+                    ..
+                }
+            )
+        });
+    }
+
+    func.basic_blocks
+        .retain(|block| !block.instructions.is_empty());
+
+    for (i, block) in func.basic_blocks.iter_mut().enumerate() {
+        block.id = BlockId(i);
+    }
+
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+}
+
+fn simplify(inst: &Instruction) -> Option<Instruction> {
+    match inst {
+        Instruction::Binary {
+            dst,
+            lhs: Value::Number(lhs),
+            op,
+            rhs: Value::Number(rhs),
+        } => Some(Instruction::Assign {
+            dst: *dst,
+            src: Value::Number(evaluate_binary(*lhs, op, *rhs)),
+        }),
+
+        Instruction::Binary { dst, lhs, op, rhs } => simplify_binary(*dst, *lhs, op, *rhs),
+
+        Instruction::Compare {
+            dst,
+            lhs: Value::Number(lhs),
+            cmp,
+            rhs: Value::Number(rhs),
+        } => Some(Instruction::Assign {
+            dst: *dst,
+            src: Value::Number(evaluate_compare(*lhs, cmp, *rhs) as i64),
+        }),
+
+        Instruction::BranchCond {
+            cond: Value::Number(1),
+            label,
+        } => Some(Instruction::Branch(*label)),
+
+        _ => None,
+    }
+}
+
+fn simplify_binary(dst: SymbolId, lhs: Value, op: &BinaryOp, rhs: Value) -> Option<Instruction> {
+    use BinaryOp::*;
+
+    match (lhs, op, rhs) {
+        (val, Add | Sub | Shl | Shr, Value::Number(0))
+        | (Value::Number(0), Add, val)
+        | (val, Mul, Value::Number(1))
+        | (Value::Number(1), Mul, val)
+        | (val, BitAnd, Value::Number(-1))
+        | (Value::Number(-1), BitAnd, val) => Some(Instruction::Assign { dst, src: val }),
+
+        (_, Mul | BitAnd, Value::Number(0)) | (Value::Number(0), Mul | BitAnd | Shl | Shr, _) => {
+            Some(Instruction::Assign {
+                dst,
+                src: Value::Number(0),
+            })
+        }
+
+        (val, Mul, Value::Number(num)) | (Value::Number(num), Mul, val)
+            if num > 0 && (num as u64).is_power_of_two() =>
+        {
+            Some(Instruction::Binary {
+                dst,
+                lhs: val,
+                op: Shl,
+                rhs: Value::Number(num.trailing_zeros() as i64),
+            })
+        }
+
+        _ => None,
+    }
+}
+
+pub fn evaluate_binary(lhs: i64, op: &BinaryOp, rhs: i64) -> i64 {
+    match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::BitAnd => lhs & rhs,
+        BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+        BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+    }
+}
+
+pub fn evaluate_compare(lhs: i64, cmp: &CompareOp, rhs: i64) -> bool {
+    match cmp {
+        CompareOp::Lt => lhs < rhs,
+        CompareOp::Le => lhs <= rhs,
+        CompareOp::Eq => lhs == rhs,
+        CompareOp::Ge => lhs >= rhs,
+        CompareOp::Gt => lhs > rhs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{count, run, transform};
+
+    fn computes(inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Binary { .. } | Instruction::Compare { .. }
+        )
+    }
+
+    #[test]
+    fn folds_constant_operands() {
+        let prog = transform(
+            r"
+define @main() {
+    %a <- 3 + 4
+    %b <- 2 << 3
+    %c <- 5 <= 4
+    %a <- %a + %b
+    %a <- %a + %c
+    call print(%a)
+    return
+}
+",
+            &[&[]],
+            |func, _| fold_constants(func),
+        );
+        // only the additions of variables are left
+        assert_eq!(run(&prog, &[]).count(computes), 2, "\n{}", prog);
+    }
+
+    #[test]
+    fn simplifies_identities() {
+        let prog = transform(
+            r"
+define @main() {
+    %x <- call input()
+    %a <- %x * 1
+    %b <- 0 + %x
+    %c <- %x & 0
+    %d <- %x * 8
+    %a <- %a + %b
+    %a <- %a + %c
+    %a <- %a + %d
+    %a <- %a + 1
+    call print(%a)
+    return
+}
+",
+            &[&[5], &[-3]],
+            |func, _| fold_constants(func),
+        );
+        let is_mul = |inst: &Instruction| {
+            matches!(
+                inst,
+                Instruction::Binary {
+                    op: BinaryOp::Mul,
+                    ..
+                }
+            )
+        };
+        assert_eq!(count(&prog, is_mul), 0, "\n{}", prog);
+        // the shift replacing the multiplication by 8 and the four additions
+        assert_eq!(run(&prog, &[5]).count(computes), 5, "\n{}", prog);
+    }
+
+    #[test]
+    fn resolves_constant_branches() {
+        let prog = transform(
+            r"
+define @main() {
+    br 1 :taken
+    br 0 :never
+    call print(3)
+    :taken
+    call print(5)
+    :never
+    return
+}
+",
+            &[&[]],
+            |func, _| fold_constants(func),
+        );
+        let is_cond = |inst: &Instruction| matches!(inst, Instruction::BranchCond { .. });
+        assert_eq!(count(&prog, is_cond), 0, "\n{}", prog);
+        assert_eq!(run(&prog, &[]).output, ["2"]);
+    }
+}
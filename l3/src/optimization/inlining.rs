@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::Interner;
+
+use crate::analysis::build_call_graph;
+use crate::globalization::fresh_symbol;
+
+const INLINE_THRESHOLD: usize = 32;
+
+pub fn inline_functions(prog: &mut Program) {
+    let call_graph = build_call_graph(prog);
+
+    for scc in call_graph.sccs() {
+        for &caller in &scc {
+            let mut instructions = Vec::new();
+            let mut inlined = false;
+
+            for inst in prog.functions[caller]
+                .basic_blocks
+                .iter()
+                .flat_map(|block| &block.instructions)
+            {
+                let (name, args, dst) = match inst {
+                    Instruction::Call {
+                        callee: Callee::Value(Value::Function(name)),
+                        args,
+                    } => (name, args, None),
+                    Instruction::CallResult {
+                        dst,
+                        callee: Callee::Value(Value::Function(name)),
+                        args,
+                    } => (name, args, Some(*dst)),
+                    _ => {
+                        instructions.push(inst.clone());
+                        continue;
+                    }
+                };
+
+                match call_graph.index_of(name.0) {
+                    Some(callee)
+                        if !scc.contains(&callee)
+                            && is_inlinable(&prog.functions[callee], args) =>
+                    {
+                        instructions.extend(expand_call(
+                            &prog.functions[callee],
+                            args,
+                            dst,
+                            &mut prog.interner,
+                        ));
+                        inlined = true;
+                    }
+                    _ => instructions.push(inst.clone()),
+                }
+            }
+
+            if inlined {
+                let func = &prog.functions[caller];
+                prog.functions[caller] =
+                    Function::new(func.name, func.params.clone(), instructions);
+            }
+        }
+    }
+}
+
+fn is_inlinable(callee: &Function, args: &[Value]) -> bool {
+    let size = callee
+        .basic_blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .filter(|inst| !matches!(inst, Instruction::Label(_)))
+        .count();
+    callee.params.len() == args.len() && size <= INLINE_THRESHOLD
+}
+
+fn expand_call(
+    callee: &Function,
+    args: &[Value],
+    dst: Option<SymbolId>,
+    interner: &mut Interner<String>,
+) -> Vec<Instruction> {
+    let return_label = fresh_symbol(
+        interner,
+        &format!("{}_return", interner.resolve(callee.name.0)),
+    );
+
+    let mut renamed: HashMap<SymbolId, SymbolId> = HashMap::new();
+    let mut rename = |sym: &mut SymbolId| {
+        *sym = *renamed.entry(*sym).or_insert_with(|| {
+            let base = interner.resolve(sym.0).clone();
+            fresh_symbol(interner, &base)
+        });
+    };
+
+    let mut params = callee.params.clone();
+    params.iter_mut().for_each(&mut rename);
+
+    let mut instructions: Vec<Instruction> = params
+        .into_iter()
+        .zip(args)
+        .map(|(param, &arg)| Instruction::Assign {
+            dst: param,
+            src: arg,
+        })
+        .collect();
+
+    for inst in callee
+        .basic_blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+    {
+        let mut inst = inst.clone();
+
+        if let Some(def) = inst.defs_mut() {
+            rename(def);
+        }
+        inst.uses_mut().into_iter().for_each(&mut rename);
+        for operand in inst.operands_mut() {
+            if let Value::Label(label) = operand {
+                rename(label);
+            }
+        }
+        if let Instruction::Label(label)
+        | Instruction::Branch(label)
+        | Instruction::BranchCond { label, .. } = &mut inst
+        {
+            rename(label);
+        }
+
+        match inst {
+            Instruction::Return => instructions.push(Instruction::Branch(return_label)),
+            Instruction::ReturnValue(val) => {
+                if let Some(dst) = dst {
+                    instructions.push(Instruction::Assign { dst, src: val });
+                }
+                instructions.push(Instruction::Branch(return_label));
+            }
+            _ => instructions.push(inst),
+        }
+    }
+
+    if instructions.last() == Some(&Instruction::Branch(return_label)) {
+        instructions.pop();
+    }
+    instructions.push(Instruction::Label(return_label));
+
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, symbol, transform_program};
+
+    fn calls(name: SymbolId) -> impl Fn(&Instruction) -> bool {
+        move |inst| match inst {
+            Instruction::Call { callee, .. } | Instruction::CallResult { callee, .. } => {
+                *callee == Callee::Value(Value::Function(name))
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn inlines_small_callees() {
+        let prog = transform_program(
+            r"
+define @main() {
+    %x <- call input()
+    %y <- call @clamp(%x)
+    %y <- call @clamp(%y)
+    call print(%y)
+    return
+}
+
+define @clamp(%v) {
+    %c <- %v < 21
+    br %c :small
+    return 21
+    :small
+    return %v
+}
+",
+            &[&[40], &[3]],
+            inline_functions,
+        );
+        let clamp = symbol(&prog, "clamp");
+        assert_eq!(run(&prog, &[40]).count(calls(clamp)), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn keeps_recursive_calls() {
+        let prog = transform_program(
+            r"
+define @main() {
+    %x <- call @count(5)
+    call print(%x)
+    return
+}
+
+define @count(%n) {
+    %c <- %n = 1
+    br %c :done
+    %n <- %n - 2
+    %r <- call @count(%n)
+    %r <- %r + 2
+    return %r
+    :done
+    return 1
+}
+",
+            &[&[]],
+            inline_functions,
+        );
+        let count = symbol(&prog, "count");
+        let func = prog
+            .functions
+            .iter()
+            .find(|func| func.name == count)
+            .unwrap();
+        let recurses = func
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .any(calls(count));
+        assert!(recurses, "\n{}", prog);
+    }
+}
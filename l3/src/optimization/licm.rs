@@ -0,0 +1,392 @@
+use std::collections::{HashMap, HashSet};
+
+use l3::*;
+use utils::{DominatorTree, Interner, Loop};
+
+use crate::analysis::{compute_dominators, compute_loops, may_alias};
+use crate::globalization::fresh_symbol;
+
+const PREHEADER: BlockId = BlockId(usize::MAX);
+
+pub fn hoist_loop_invariants(func: &mut Function, interner: &mut Interner<String>) {
+    let mut visited = HashSet::new();
+
+    loop {
+        let dt = compute_dominators(func);
+        let loops = compute_loops(func, &dt);
+
+        let Some(loop_) = loops
+            .loops()
+            .iter()
+            .find(|loop_| visited.insert(header_label(func, loop_.header())))
+        else {
+            break;
+        };
+
+        let invariants = find_invariants(func, &dt, loop_);
+        if invariants.is_empty() {
+            continue;
+        }
+
+        let hoisted = invariants
+            .iter()
+            .map(|&(id, i)| func.basic_blocks[id.0].instructions[i].clone())
+            .collect();
+
+        if let Some(preheader) = insert_preheader(func, loop_, hoisted, interner) {
+            let mut removed = invariants;
+            removed.sort_by_key(|&(id, i)| (id.0, i));
+
+            for &(id, i) in removed.iter().rev() {
+                let id = if id.0 >= preheader.0 { id.0 + 1 } else { id.0 };
+                func.basic_blocks[id].instructions.remove(i);
+            }
+        }
+    }
+}
+
+pub fn header_label(func: &Function, header: BlockId) -> SymbolId {
+    match func.basic_blocks[header.0].instructions.first() {
+        Some(Instruction::Label(label)) => *label,
+        _ => unreachable!("loop header without a label"),
+    }
+}
+
+fn find_invariants(
+    func: &Function,
+    dt: &DominatorTree<BlockId>,
+    loop_: &Loop<BlockId>,
+) -> Vec<(BlockId, usize)> {
+    let mut def_blocks = HashMap::new();
+    let mut def_insts: HashMap<SymbolId, &Instruction> = HashMap::new();
+
+    for block in &func.basic_blocks {
+        for inst in &block.instructions {
+            if let Some(def) = inst.defs() {
+                def_blocks.insert(def, block.id);
+                def_insts.insert(def, inst);
+            }
+        }
+    }
+
+    let def_of = |var, ()| def_insts.get(&var).map(|&inst| ((), inst));
+
+    let loop_insts = || {
+        loop_
+            .basic_blocks()
+            .iter()
+            .flat_map(|id| &func.basic_blocks[id.0].instructions)
+    };
+
+    let has_call = loop_insts().any(|inst| {
+        matches!(
+            inst,
+            Instruction::Call { .. }
+                | Instruction::CallResult { .. }
+                | Instruction::TailCall { .. }
+        )
+    });
+
+    let stores: Vec<SymbolId> = loop_insts()
+        .filter_map(|inst| match inst {
+            Instruction::Store { dst, .. } => Some(*dst),
+            _ => None,
+        })
+        .collect();
+
+    let exits: Vec<BlockId> = loop_
+        .basic_blocks()
+        .iter()
+        .copied()
+        .filter(|&id| {
+            func.cfg.successors[id.0]
+                .iter()
+                .any(|&succ| !loop_.contains(succ))
+        })
+        .collect();
+
+    let mut invariant = HashSet::new();
+    let mut order = Vec::new();
+
+    loop {
+        let mut changed = false;
+
+        for &id in loop_.basic_blocks() {
+            let instructions = &func.basic_blocks[id.0].instructions;
+
+            // never hoist the last instruction so that no block is left empty
+            for (i, inst) in instructions.iter().enumerate().take(instructions.len() - 1) {
+                let Some(dst) = inst.defs() else {
+                    continue;
+                };
+                if invariant.contains(&dst) {
+                    continue;
+                }
+
+                let hoistable = match inst {
+                    Instruction::Binary { .. } | Instruction::Compare { .. } => true,
+                    Instruction::Load { src, .. } => {
+                        !has_call
+                            && !exits.is_empty()
+                            && exits.iter().all(|&exit| dt.dominates(id, exit))
+                            && stores
+                                .iter()
+                                .all(|&addr| !may_alias(def_of, ((), *src), ((), addr)))
+                    }
+                    _ => false,
+                };
+
+                if hoistable
+                    && inst.uses().iter().all(|use_| {
+                        invariant.contains(use_)
+                            || def_blocks
+                                .get(use_)
+                                .is_none_or(|&block| !loop_.contains(block))
+                    })
+                {
+                    invariant.insert(dst);
+                    order.push((id, i));
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    order
+}
+
+// Inserts a block holding `instructions` that becomes the only entry into the
+// loop, and returns its id. Block ids at or after it are shifted by one.
+pub fn insert_preheader(
+    func: &mut Function,
+    loop_: &Loop<BlockId>,
+    instructions: Vec<Instruction>,
+    interner: &mut Interner<String>,
+) -> Option<BlockId> {
+    let header = loop_.header();
+    let label = header_label(func, header);
+
+    let outside_preds: Vec<BlockId> = func.cfg.predecessors[header.0]
+        .iter()
+        .copied()
+        .filter(|&pred| !loop_.contains(pred))
+        .collect();
+    if outside_preds.is_empty() {
+        return None;
+    }
+
+    // a latch that falls through into the header via a conditional branch
+    // cannot be redirected, so the preheader goes at the end instead
+    let falls_through = |block: &BasicBlock| {
+        !matches!(
+            block.instructions.last(),
+            Some(Instruction::Branch(_) | Instruction::Return | Instruction::ReturnValue(_))
+        )
+    };
+    let prev = header.0.checked_sub(1).map(BlockId);
+    let at_end = prev.is_some_and(|prev| {
+        loop_.contains(prev)
+            && matches!(
+                func.basic_blocks[prev.0].instructions.last(),
+                Some(Instruction::BranchCond { .. })
+            )
+    });
+    if at_end && func.basic_blocks.last().is_some_and(falls_through) {
+        return None;
+    }
+
+    let preheader_label = fresh_symbol(interner, "preheader");
+    let mut preheader = vec![Instruction::Label(preheader_label)];
+
+    for inst in &mut func.basic_blocks[header.0].instructions {
+        let Instruction::Phi { dst, args } = inst else {
+            continue;
+        };
+
+        let (outside, mut inside): (Vec<_>, Vec<_>) = args
+            .iter()
+            .cloned()
+            .partition(|(pred, _)| outside_preds.contains(pred));
+
+        let value = if outside.iter().all(|(_, value)| *value == outside[0].1) {
+            outside[0].1
+        } else {
+            let base = interner.resolve(dst.0).clone();
+            let merged = fresh_symbol(interner, &base);
+            preheader.push(Instruction::Phi {
+                dst: merged,
+                args: outside,
+            });
+            Value::Variable(merged)
+        };
+
+        inside.push((PREHEADER, value));
+        *args = inside;
+    }
+
+    preheader.extend(instructions);
+
+    for &pred in &outside_preds {
+        match func.basic_blocks[pred.0].instructions.last_mut() {
+            Some(Instruction::Branch(target))
+            | Some(Instruction::BranchCond { label: target, .. })
+                if *target == label =>
+            {
+                *target = preheader_label;
+            }
+            _ => (),
+        }
+    }
+
+    let position = if at_end {
+        preheader.push(Instruction::Branch(label));
+        func.basic_blocks.len()
+    } else {
+        if let Some(prev) = prev
+            && loop_.contains(prev)
+            && falls_through(&func.basic_blocks[prev.0])
+        {
+            func.basic_blocks[prev.0]
+                .instructions
+                .push(Instruction::Branch(label));
+        }
+        header.0
+    };
+
+    func.basic_blocks.insert(
+        position,
+        BasicBlock {
+            id: PREHEADER,
+            instructions: preheader,
+        },
+    );
+
+    let renumber = |id: BlockId| {
+        if id == PREHEADER {
+            BlockId(position)
+        } else if id.0 >= position {
+            BlockId(id.0 + 1)
+        } else {
+            id
+        }
+    };
+
+    for (i, block) in func.basic_blocks.iter_mut().enumerate() {
+        block.id = BlockId(i);
+        for inst in &mut block.instructions {
+            if let Instruction::Phi { args, .. } = inst {
+                for (pred, _) in args {
+                    *pred = renumber(*pred);
+                }
+            }
+        }
+    }
+
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+
+    Some(BlockId(position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssa::construct_ssa;
+    use crate::testing::{run, transform};
+
+    fn hoisted(source: &str, inputs: &[&[i64]]) -> Program {
+        transform(source, inputs, |func, interner| {
+            construct_ssa(func, interner);
+            hoist_loop_invariants(func, interner);
+        })
+    }
+
+    // Copies the word at `%p + 8` to `%p + store_offset` as many times as the
+    // input asks for.
+    fn copy_loop(store_offset: i64, extra: &str) -> String {
+        format!(
+            r"
+define @main() {{
+    %n <- call input()
+    %p <- call allocate(5, 3)
+    %a <- %p + 8
+    %b <- %p + {}
+    %i <- 0
+    :loop
+    %v <- load %a
+    %v <- %v + 2
+    store %b <- %v
+    {}
+    %i <- %i + 1
+    %c <- %i < %n
+    br %c :loop
+    call print(%p)
+    return
+}}
+",
+            store_offset, extra
+        )
+    }
+
+    fn is_load(inst: &Instruction) -> bool {
+        matches!(inst, Instruction::Load { .. })
+    }
+
+    #[test]
+    fn hoists_invariant_arithmetic() {
+        let prog = hoisted(
+            r"
+define @main() {
+    %n <- call input()
+    %k <- call input()
+    %i <- 0
+    %s <- 1
+    :loop
+    %c <- %i < %n
+    br %c :body
+    call print(%s)
+    return
+    :body
+    %m <- %k * 4
+    %d <- %m + 2
+    %s <- %s + %d
+    %i <- %i + 1
+    br :loop
+}
+",
+            &[&[3, 5], &[0, 5]],
+        );
+        let is_mul = |inst: &Instruction| {
+            matches!(
+                inst,
+                Instruction::Binary {
+                    op: BinaryOp::Mul,
+                    ..
+                }
+            )
+        };
+        // the loop runs 7 times, since %n is read encoded
+        assert_eq!(run(&prog, &[3, 5]).count(is_mul), 1, "\n{}", prog);
+    }
+
+    #[test]
+    fn hoists_loads_from_disjoint_addresses() {
+        let prog = hoisted(&copy_loop(16, ""), &[&[3]]);
+        assert_eq!(run(&prog, &[3]).count(is_load), 1, "\n{}", prog);
+    }
+
+    #[test]
+    fn keeps_loads_clobbered_in_the_loop() {
+        let prog = hoisted(&copy_loop(8, ""), &[&[3]]);
+        assert_eq!(run(&prog, &[3]).count(is_load), 7, "\n{}", prog);
+    }
+
+    #[test]
+    fn keeps_loads_in_loops_with_calls() {
+        let prog = hoisted(&copy_loop(16, "call print(%v)"), &[&[3]]);
+        assert_eq!(run(&prog, &[3]).count(is_load), 7, "\n{}", prog);
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::{BitVector, Dataflow, Direction, Interner, solve};
+
+use crate::analysis::{ExpressionResult, compute_available_exprs, compute_very_busy_exprs};
+use crate::globalization::fresh_symbol;
+use crate::optimization::simplification::{falls_through, label_of};
+
+// Lazy code motion over the expressions of a function without phis. Every
+// expression that becomes redundant somewhere is given a temporary, which all
+// its remaining computations write and its redundant ones read.
+pub fn eliminate_partial_redundancies(func: &mut Function, interner: &mut Interner<String>) {
+    // the entry must have no predecessors so that nothing but the implicit
+    // edge into it reaches it
+    if !func.cfg.predecessors[0].is_empty() {
+        return;
+    }
+
+    let available = compute_available_exprs(func);
+    let anticipated = compute_very_busy_exprs(func);
+    let later = LaterAnalysis::new(func, &available, &anticipated);
+    let (later_enter, later_exit) = solve(&func.cfg, &later);
+
+    let num_blocks = func.basic_blocks.len();
+    let later_in: Vec<BitVector> = (0..num_blocks)
+        .map(|i| {
+            let mut later_in = later_enter[i].clone();
+            later_in.intersection(&later.ant_in[i]);
+            later_in
+        })
+        .collect();
+
+    let deleted: Vec<BitVector> = (0..num_blocks)
+        .map(|i| {
+            let mut deleted = later.upward_exposed[i].clone();
+            deleted.difference(&later_in[i]);
+            deleted
+        })
+        .collect();
+
+    let mut redundant = BitVector::new(later.num_exprs);
+    for deleted in &deleted {
+        redundant.union(deleted);
+    }
+    if !redundant.any() {
+        return;
+    }
+
+    let mut inserted = Vec::new();
+    for (i, exit) in later_exit.iter().enumerate() {
+        for &succ in &func.cfg.successors[i] {
+            let mut insert = exit.clone();
+            insert.intersection(&later.ant_in[succ.0]);
+            insert.difference(&later_in[succ.0]);
+            insert.intersection(&redundant);
+            if insert.any() {
+                inserted.push((i, succ.0, insert));
+            }
+        }
+    }
+
+    let is_critical =
+        |i: usize, j: usize| func.cfg.successors[i].len() > 1 && func.cfg.predecessors[j].len() > 1;
+    let is_branch_edge = |i: usize, j: usize| match func.basic_blocks[i].instructions.last() {
+        Some(Instruction::BranchCond { label, .. }) => {
+            Some(*label) == label_of(&func.basic_blocks[j])
+        }
+        _ => false,
+    };
+
+    // split blocks reached by a branch are parked after a block that never
+    // falls through, so that they cannot change where any other block goes
+    let anchor = func
+        .basic_blocks
+        .iter()
+        .rposition(|block| !falls_through(block));
+    if anchor.is_none()
+        && inserted
+            .iter()
+            .any(|&(i, j, _)| is_critical(i, j) && is_branch_edge(i, j))
+    {
+        return;
+    }
+
+    let table = &available.table;
+    let temps: HashMap<usize, SymbolId> = redundant
+        .iter()
+        .map(|k| (k, fresh_symbol(interner, "pre")))
+        .collect();
+    let compute = |k: usize| table.interner.resolve(k).to_instruction(temps[&k]);
+
+    let mut at_start: HashMap<usize, Vec<Instruction>> = HashMap::new();
+    let mut at_end: HashMap<usize, Vec<Instruction>> = HashMap::new();
+    let mut fallthrough_splits: HashMap<usize, Vec<Instruction>> = HashMap::new();
+    let mut branch_splits = Vec::new();
+    let mut retargets = Vec::new();
+
+    for (i, j, insert) in inserted {
+        let instructions = insert.iter().map(compute);
+
+        if func.cfg.successors[i].len() == 1 {
+            at_end.entry(i).or_default().extend(instructions);
+        } else if func.cfg.predecessors[j].len() == 1 {
+            at_start.entry(j).or_default().extend(instructions);
+        } else if is_branch_edge(i, j) {
+            let label = fresh_symbol(interner, "split");
+            let target = label_of(&func.basic_blocks[j]).unwrap();
+            let mut block = vec![Instruction::Label(label)];
+            block.extend(instructions);
+            block.push(Instruction::Branch(target));
+
+            retargets.push((i, label));
+            branch_splits.push(block);
+        } else {
+            fallthrough_splits.insert(i, instructions.collect());
+        }
+    }
+
+    for (i, label) in retargets {
+        if let Some(Instruction::BranchCond { label: target, .. }) =
+            func.basic_blocks[i].instructions.last_mut()
+        {
+            *target = label;
+        }
+    }
+
+    for (i, block) in func.basic_blocks.iter_mut().enumerate() {
+        let mut killed = BitVector::new(later.num_exprs);
+        let mut instructions = Vec::with_capacity(block.instructions.len());
+
+        for inst in block.instructions.drain(..) {
+            let kills: Vec<usize> = table.killed_by(&inst).collect();
+
+            match (table.computed_by(&inst), inst.defs()) {
+                (Some(k), Some(dst)) if redundant.test(k) => {
+                    if !deleted[i].test(k) || killed.test(k) {
+                        instructions.push(compute(k));
+                    }
+                    instructions.push(Instruction::Assign {
+                        dst,
+                        src: Value::Variable(temps[&k]),
+                    });
+                }
+                _ => instructions.push(inst),
+            }
+
+            killed.set_from(kills.into_iter());
+        }
+
+        if let Some(insts) = at_start.remove(&i) {
+            let position = label_of(block).map_or(0, |_| 1);
+            instructions.splice(position..position, insts);
+        }
+        if let Some(insts) = at_end.remove(&i) {
+            let position = match instructions.last() {
+                Some(Instruction::Branch(_) | Instruction::BranchCond { .. }) => {
+                    instructions.len() - 1
+                }
+                _ => instructions.len(),
+            };
+            instructions.splice(position..position, insts);
+        }
+
+        block.instructions = instructions;
+    }
+
+    let mut basic_blocks = Vec::new();
+    for (i, block) in func.basic_blocks.drain(..).enumerate() {
+        basic_blocks.push(block.instructions);
+        if let Some(insts) = fallthrough_splits.remove(&i) {
+            basic_blocks.push(insts);
+        }
+        if Some(i) == anchor {
+            basic_blocks.append(&mut branch_splits);
+        }
+    }
+
+    func.basic_blocks = basic_blocks
+        .into_iter()
+        .enumerate()
+        .map(|(i, instructions)| BasicBlock {
+            id: BlockId(i),
+            instructions,
+        })
+        .collect();
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+}
+
+// An expression is later on entry to a block if its computation can be
+// postponed up to there. Later(i, j) is ANTIN(j) intersected with the exit
+// set of i, and LATERIN(j) is the meet of those intersected with ANTIN(j).
+#[derive(Debug)]
+struct LaterAnalysis {
+    num_exprs: usize,
+    ant_in: Vec<BitVector>,
+    earliest: Vec<BitVector>,
+    upward_exposed: Vec<BitVector>,
+}
+
+impl LaterAnalysis {
+    pub fn new(
+        func: &Function,
+        available: &ExpressionResult,
+        anticipated: &ExpressionResult,
+    ) -> Self {
+        let table = &available.table;
+        let num_exprs = table.interner.len();
+        let num_blocks = func.basic_blocks.len();
+        let mut ant_in = Vec::with_capacity(num_blocks);
+        let mut earliest = Vec::with_capacity(num_blocks);
+        let mut upward_exposed = vec![BitVector::new(num_exprs); num_blocks];
+
+        for (i, block) in func.basic_blocks.iter().enumerate() {
+            let mut kill = BitVector::new(num_exprs);
+            for inst in &block.instructions {
+                if let Some(k) = table.computed_by(inst)
+                    && !kill.test(k)
+                {
+                    upward_exposed[i].set(k);
+                }
+                kill.set_from(table.killed_by(inst));
+            }
+
+            // the part of EARLIEST(i, j) that does not depend on j:
+            // !AVAILOUT(i) & (KILL(i) | !ANTOUT(i))
+            let mut early = BitVector::new(num_exprs);
+            early.set_from(0..num_exprs);
+            early.difference(anticipated.out[i].last().unwrap());
+            early.union(&kill);
+            early.difference(available.out[i].last().unwrap());
+
+            ant_in.push(anticipated.in_[i][0].clone());
+            earliest.push(early);
+        }
+
+        LaterAnalysis {
+            num_exprs,
+            ant_in,
+            earliest,
+            upward_exposed,
+        }
+    }
+}
+
+impl Dataflow for LaterAnalysis {
+    type Block = BlockId;
+
+    const DIRECTION: Direction = Direction::Forward;
+
+    // the edge into the entry is the earliest point for everything
+    // anticipated there
+    fn boundary(&self) -> BitVector {
+        self.ant_in[0].clone()
+    }
+
+    fn top(&self) -> BitVector {
+        let mut all = BitVector::new(self.num_exprs);
+        all.set_from(0..self.num_exprs);
+        all
+    }
+
+    fn meet(&self, current: &mut BitVector, other: &BitVector) {
+        current.intersection(other);
+    }
+
+    fn transfer(&self, input: &BitVector, id: BlockId) -> BitVector {
+        let mut output = input.clone();
+        output.intersection(&self.ant_in[id.0]);
+        output.difference(&self.upward_exposed[id.0]);
+        output.union(&self.earliest[id.0]);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, transform};
+
+    const INPUTS: [&[i64]; 2] = [&[1, 2], &[2, 1]];
+
+    fn eliminated(source: &str, inputs: &[&[i64]]) -> Program {
+        transform(source, inputs, eliminate_partial_redundancies)
+    }
+
+    fn is_and(inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Binary {
+                op: BinaryOp::BitAnd,
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn inserts_on_the_path_missing_the_expression() {
+        let prog = eliminated(
+            r"
+define @main() {
+    %a <- call input()
+    %b <- call input()
+    %c <- %a < %b
+    br %c :then
+    %x <- %a & %b
+    br :join
+    :then
+    %y <- 1
+    :join
+    %z <- %a & %b
+    call print(%z)
+    return
+}
+",
+            &INPUTS,
+        );
+        for input in INPUTS {
+            assert_eq!(run(&prog, input).count(is_and), 1, "\n{}", prog);
+        }
+    }
+
+    #[test]
+    fn splits_critical_branch_edges() {
+        let prog = eliminated(
+            r"
+define @main() {
+    %a <- call input()
+    %b <- call input()
+    %c <- %a < %b
+    br %c :join
+    %x <- %a & %b
+    call print(%x)
+    :join
+    %z <- %a & %b
+    call print(%z)
+    return
+}
+",
+            &INPUTS,
+        );
+        for input in INPUTS {
+            assert_eq!(run(&prog, input).count(is_and), 1, "\n{}", prog);
+        }
+
+        // the branch now goes through a block computing the expression
+        let blocks = &prog.functions[0].basic_blocks;
+        let target = blocks
+            .iter()
+            .find_map(|block| match block.instructions.last() {
+                Some(Instruction::BranchCond { label, .. }) => Some(*label),
+                _ => None,
+            })
+            .unwrap();
+        let split = blocks
+            .iter()
+            .find(|block| block.instructions.first() == Some(&Instruction::Label(target)))
+            .unwrap();
+        assert!(
+            matches!(
+                split.instructions.as_slice(),
+                [Instruction::Label(_), inst, Instruction::Branch(_)] if is_and(inst)
+            ),
+            "\n{}",
+            prog
+        );
+    }
+
+    #[test]
+    fn hoists_loop_invariants() {
+        let prog = eliminated(
+            r"
+define @main() {
+    %a <- call input()
+    %b <- call input()
+    %i <- 0
+    :loop
+    %x <- %a & %b
+    %i <- %i + 1
+    %c <- %i < 3
+    br %c :loop
+    call print(%x)
+    return
+}
+",
+            &INPUTS,
+        );
+        assert_eq!(run(&prog, &[1, 2]).count(is_and), 1, "\n{}", prog);
+    }
+}
@@ -0,0 +1,152 @@
+use l3::*;
+
+use crate::analysis::{ReachingDefResult, compute_reaching_def};
+
+pub fn propagate_copies(func: &mut Function) {
+    loop {
+        let reaching_def = compute_reaching_def(func);
+        let mut changed = false;
+
+        for (b, j, var, src) in find_replacements(func, &reaching_def) {
+            let inst = &mut func.basic_blocks[b].instructions[j];
+
+            match src {
+                Value::Variable(src) => {
+                    for use_ in inst.uses_mut() {
+                        if *use_ == var {
+                            *use_ = src;
+                            changed = true;
+                        }
+                    }
+                }
+
+                _ => {
+                    for operand in inst.operands_mut() {
+                        if *operand == Value::Variable(var) {
+                            *operand = src;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn find_replacements(
+    func: &Function,
+    reaching_def: &ReachingDefResult,
+) -> Vec<(usize, usize, SymbolId, Value)> {
+    let reaching = |b: usize, j: usize, var: SymbolId| -> Vec<usize> {
+        reaching_def.in_[b][j]
+            .iter()
+            .filter(|&k| reaching_def.insts[k].defs() == Some(var))
+            .collect()
+    };
+
+    let mut replacements = Vec::new();
+
+    for (b, block) in func.basic_blocks.iter().enumerate() {
+        for (j, inst) in block.instructions.iter().enumerate() {
+            let mut uses = inst.uses();
+            uses.dedup();
+
+            for var in uses {
+                let [def] = reaching(b, j, var)[..] else {
+                    continue;
+                };
+
+                let Instruction::Assign { src, .. } = &reaching_def.insts[def] else {
+                    continue;
+                };
+
+                // the copy may be replaced when the definitions of its source
+                // reaching the use are the ones that reached the copy
+                let safe = match *src {
+                    Value::Number(_) => true,
+                    Value::Variable(src) => {
+                        let (copy_block, copy_index) = *reaching_def.interner.resolve(def);
+                        src != var && reaching(copy_block.0, copy_index, src) == reaching(b, j, src)
+                    }
+                    _ => false,
+                };
+
+                if safe {
+                    replacements.push((b, j, var, *src));
+                }
+            }
+        }
+    }
+
+    replacements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{def, symbol, transform};
+
+    fn propagated(source: &str) -> Program {
+        transform(source, &[&[1], &[4]], |func, _| propagate_copies(func))
+    }
+
+    #[test]
+    fn propagates_copies_and_constants() {
+        let prog = propagated(
+            r"
+define @main() {
+    %a <- call input()
+    %b <- %a
+    %c <- 6
+    %d <- %b + %c
+    call print(%d)
+    return
+}
+",
+        );
+        let sum = Instruction::Binary {
+            dst: symbol(&prog, "d"),
+            lhs: Value::Variable(symbol(&prog, "a")),
+            op: BinaryOp::Add,
+            rhs: Value::Number(6),
+        };
+        assert_eq!(*def(&prog, "d"), sum, "\n{}", prog);
+    }
+
+    #[test]
+    fn keeps_copy_whose_source_is_redefined() {
+        propagated(
+            r"
+define @main() {
+    %a <- call input()
+    %b <- %a
+    %a <- %a + 2
+    call print(%b)
+    return
+}
+",
+        );
+    }
+
+    #[test]
+    fn tells_apart_identical_redefinitions() {
+        // both increments of %y read the same, but the copy sees the first
+        // one and the print comes after the second
+        propagated(
+            r"
+define @main() {
+    %y <- call input()
+    %y <- %y + 2
+    %x <- %y
+    %y <- %y + 2
+    call print(%x)
+    return
+}
+",
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::{HashMap, HashSet};
+
+use l3::*;
+use utils::{BitVector, Interner};
+
+use crate::globalization::fresh_symbol;
+use crate::optimization::dce::remove_unreachable_blocks;
+use crate::optimization::folding::{evaluate_binary, evaluate_compare};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lattice {
+    Top,
+    Constant(i64),
+    Bottom,
+}
+
+impl Lattice {
+    fn meet(self, other: Self) -> Self {
+        match (self, other) {
+            (Lattice::Top, val) | (val, Lattice::Top) => val,
+            (Lattice::Constant(a), Lattice::Constant(b)) if a == b => Lattice::Constant(a),
+            _ => Lattice::Bottom,
+        }
+    }
+}
+
+struct ConstantPropagation<'a> {
+    func: &'a Function,
+    block_ids: HashMap<SymbolId, BlockId>,
+    values: HashMap<SymbolId, Lattice>,
+    users: HashMap<SymbolId, Vec<(BlockId, usize)>>,
+    visited: BitVector,
+    executable: HashSet<(BlockId, BlockId)>,
+    flow_worklist: Vec<(Option<BlockId>, BlockId)>,
+    ssa_worklist: Vec<(BlockId, usize)>,
+}
+
+impl<'a> ConstantPropagation<'a> {
+    fn new(func: &'a Function) -> Self {
+        let block_ids = func
+            .basic_blocks
+            .iter()
+            .filter_map(|block| match block.instructions.first() {
+                Some(Instruction::Label(label)) => Some((*label, block.id)),
+                _ => None,
+            })
+            .collect();
+
+        let mut values = HashMap::new();
+        let mut users: HashMap<SymbolId, Vec<(BlockId, usize)>> = HashMap::new();
+
+        for block in &func.basic_blocks {
+            for (i, inst) in block.instructions.iter().enumerate() {
+                if let Some(def) = inst.defs() {
+                    values.insert(def, Lattice::Top);
+                }
+                for use_ in inst.uses() {
+                    users.entry(use_).or_default().push((block.id, i));
+                }
+            }
+        }
+
+        Self {
+            func,
+            block_ids,
+            values,
+            users,
+            visited: BitVector::new(func.basic_blocks.len()),
+            executable: HashSet::new(),
+            flow_worklist: vec![(None, BlockId(0))],
+            ssa_worklist: Vec::new(),
+        }
+    }
+
+    fn solve(&mut self) {
+        loop {
+            if let Some((pred, id)) = self.flow_worklist.pop() {
+                if let Some(pred) = pred
+                    && !self.executable.insert((pred, id))
+                {
+                    continue;
+                }
+
+                let block = &self.func.basic_blocks[id.0];
+                let first_visit = !self.visited.test(id.0);
+                self.visited.set(id.0);
+
+                for (i, inst) in block.instructions.iter().enumerate() {
+                    if first_visit || matches!(inst, Instruction::Phi { .. }) {
+                        self.visit(id, i);
+                    }
+                }
+
+                if first_visit
+                    && !matches!(
+                        block.instructions.last(),
+                        Some(
+                            Instruction::Branch(_)
+                                | Instruction::BranchCond { .. }
+                                | Instruction::Return
+                                | Instruction::ReturnValue(_)
+                                | Instruction::TailCall { .. }
+                        )
+                    )
+                {
+                    self.fall_through(id);
+                }
+            } else if let Some((id, i)) = self.ssa_worklist.pop() {
+                if self.visited.test(id.0) {
+                    self.visit(id, i);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn visit(&mut self, id: BlockId, i: usize) {
+        match &self.func.basic_blocks[id.0].instructions[i] {
+            Instruction::Phi { dst, args } => {
+                let val = args
+                    .iter()
+                    .filter(|(pred, _)| self.executable.contains(&(*pred, id)))
+                    .fold(Lattice::Top, |acc, (_, arg)| acc.meet(self.value_of(arg)));
+                self.update(*dst, val);
+            }
+
+            Instruction::Assign { dst, src } => self.update(*dst, self.value_of(src)),
+
+            Instruction::Binary { dst, lhs, op, rhs } => {
+                let val = match (self.value_of(lhs), self.value_of(rhs)) {
+                    (Lattice::Constant(lhs), Lattice::Constant(rhs)) => {
+                        Lattice::Constant(evaluate_binary(lhs, op, rhs))
+                    }
+                    (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                    _ => Lattice::Top,
+                };
+                self.update(*dst, val);
+            }
+
+            Instruction::Compare { dst, lhs, cmp, rhs } => {
+                let val = match (self.value_of(lhs), self.value_of(rhs)) {
+                    (Lattice::Constant(lhs), Lattice::Constant(rhs)) => {
+                        Lattice::Constant(evaluate_compare(lhs, cmp, rhs) as i64)
+                    }
+                    (Lattice::Bottom, _) | (_, Lattice::Bottom) => Lattice::Bottom,
+                    _ => Lattice::Top,
+                };
+                self.update(*dst, val);
+            }
+
+            Instruction::Load { dst, .. } | Instruction::CallResult { dst, .. } => {
+                self.update(*dst, Lattice::Bottom)
+            }
+
+            Instruction::Branch(label) => {
+                self.flow_worklist.push((Some(id), self.block_ids[label]))
+            }
+
+            Instruction::BranchCond { cond, label } => {
+                let target = self.block_ids[label];
+                match self.value_of(cond) {
+                    Lattice::Constant(1) => self.flow_worklist.push((Some(id), target)),
+                    Lattice::Constant(_) => self.fall_through(id),
+                    Lattice::Bottom => {
+                        self.flow_worklist.push((Some(id), target));
+                        self.fall_through(id);
+                    }
+                    Lattice::Top => (),
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    fn fall_through(&mut self, id: BlockId) {
+        if id.0 + 1 < self.func.basic_blocks.len() {
+            self.flow_worklist.push((Some(id), BlockId(id.0 + 1)));
+        }
+    }
+
+    fn value_of(&self, val: &Value) -> Lattice {
+        match val {
+            Value::Number(num) => Lattice::Constant(*num),
+            Value::Variable(var) => self.values.get(var).copied().unwrap_or(Lattice::Bottom),
+            _ => Lattice::Bottom,
+        }
+    }
+
+    fn update(&mut self, var: SymbolId, val: Lattice) {
+        let old = self.values[&var];
+        let new = old.meet(val);
+
+        if new != old {
+            self.values.insert(var, new);
+            if let Some(users) = self.users.get(&var) {
+                self.ssa_worklist.extend(users.iter().copied());
+            }
+        }
+    }
+}
+
+pub fn propagate_constants(func: &mut Function, interner: &mut Interner<String>) {
+    let mut analysis = ConstantPropagation::new(func);
+    analysis.solve();
+
+    let ConstantPropagation {
+        values, visited, ..
+    } = analysis;
+
+    let constant = |var: &SymbolId| match values.get(var) {
+        Some(Lattice::Constant(num)) => Some(*num),
+        _ => None,
+    };
+
+    let num_blocks = func.basic_blocks.len();
+
+    for i in 0..num_blocks {
+        if !visited.test(i) {
+            continue;
+        }
+
+        for inst in &mut func.basic_blocks[i].instructions {
+            if let Some(num) = inst.defs().as_ref().and_then(constant)
+                && matches!(
+                    inst,
+                    Instruction::Assign { .. }
+                        | Instruction::Binary { .. }
+                        | Instruction::Compare { .. }
+                        | Instruction::Phi { .. }
+                )
+            {
+                *inst = Instruction::Assign {
+                    dst: inst.defs().unwrap(),
+                    src: Value::Number(num),
+                };
+            }
+
+            for operand in inst.operands_mut() {
+                if let Value::Variable(var) = operand
+                    && let Some(num) = constant(var)
+                {
+                    *operand = Value::Number(num);
+                }
+            }
+        }
+
+        let block = &func.basic_blocks[i];
+        let Some(&Instruction::BranchCond {
+            cond: Value::Number(cond),
+            label,
+        }) = block.instructions.last()
+        else {
+            continue;
+        };
+
+        if cond == 1 {
+            *func.basic_blocks[i].instructions.last_mut().unwrap() = Instruction::Branch(label);
+        } else if block.instructions.len() > 1 {
+            func.basic_blocks[i].instructions.pop();
+        } else if i + 1 < num_blocks {
+            let next = &mut func.basic_blocks[i + 1].instructions;
+            let label = match next.first() {
+                Some(Instruction::Label(label)) => *label,
+                _ => {
+                    let label = fresh_symbol(interner, "fallthrough");
+                    next.insert(0, Instruction::Label(label));
+                    label
+                }
+            };
+            *func.basic_blocks[i].instructions.last_mut().unwrap() = Instruction::Branch(label);
+        }
+    }
+
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+
+    for block in &mut func.basic_blocks {
+        let preds = &func.cfg.predecessors[block.id.0];
+        for inst in &mut block.instructions {
+            if let Instruction::Phi { args, .. } = inst {
+                args.retain(|(pred, _)| preds.contains(pred));
+            }
+        }
+    }
+
+    remove_unreachable_blocks(func);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssa::construct_ssa;
+    use crate::testing::{count, transform};
+
+    fn propagated(source: &str, inputs: &[&[i64]]) -> Program {
+        transform(source, inputs, |func, interner| {
+            construct_ssa(func, interner);
+            propagate_constants(func, interner);
+        })
+    }
+
+    fn prints(val: Value) -> impl Fn(&Instruction) -> bool {
+        move |inst| {
+            matches!(
+                inst,
+                Instruction::Call {
+                    callee: Callee::Print,
+                    args,
+                } if args[..] == [val]
+            )
+        }
+    }
+
+    #[test]
+    fn folds_branches_on_constants() {
+        let prog = propagated(
+            r"
+define @main() {
+    %x <- 4
+    %c <- %x < 10
+    br %c :small
+    %y <- 1
+    br :join
+    :small
+    %y <- 3
+    :join
+    %y <- %y << 1
+    %y <- %y + 1
+    call print(%y)
+    return
+}
+",
+            &[&[]],
+        );
+        let is_cond = |inst: &Instruction| matches!(inst, Instruction::BranchCond { .. });
+        assert_eq!(count(&prog, is_cond), 0, "\n{}", prog);
+        assert_eq!(count(&prog, prints(Value::Number(7))), 1, "\n{}", prog);
+    }
+
+    #[test]
+    fn meets_to_unknown_on_different_constants() {
+        let prog = propagated(
+            r"
+define @main() {
+    %c <- call input()
+    %x <- 1
+    br %c :other
+    br :join
+    :other
+    %x <- 3
+    :join
+    call print(%x)
+    return
+}
+",
+            &[&[0], &[1]],
+        );
+        let prints_constant = |inst: &Instruction| {
+            matches!(
+                inst,
+                Instruction::Call {
+                    callee: Callee::Print,
+                    args,
+                } if matches!(args[..], [Value::Number(_)])
+            )
+        };
+        assert_eq!(count(&prog, prints_constant), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn ignores_values_from_unexecuted_back_edges() {
+        let prog = propagated(
+            r"
+define @main() {
+    %x <- 5
+    %i <- 0
+    :loop
+    %c <- %i < 3
+    br %c :body
+    call print(%x)
+    return
+    :body
+    %i <- %i + 1
+    %d <- %x = 5
+    br %d :loop
+    %x <- 9
+    br :loop
+}
+",
+            &[&[]],
+        );
+        let assigns_9 = |inst: &Instruction| {
+            matches!(
+                inst,
+                Instruction::Assign {
+                    src: Value::Number(9),
+                    ..
+                }
+            )
+        };
+        assert_eq!(count(&prog, assigns_9), 0, "\n{}", prog);
+        assert_eq!(count(&prog, prints(Value::Number(5))), 1, "\n{}", prog);
+    }
+}
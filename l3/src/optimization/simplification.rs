@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+
+use l3::*;
+
+use crate::optimization::remove_unreachable_blocks;
+
+// Expects a function without phis, i.e. before SSA construction or after
+// its destruction.
+pub fn simplify_cfg(func: &mut Function) {
+    loop {
+        let mut changed = thread_jumps(func);
+        changed |= remove_redundant_branches(func);
+        rebuild(func);
+
+        while merge_blocks(func) {
+            rebuild(func);
+            changed = true;
+        }
+
+        let num_blocks = func.basic_blocks.len();
+        remove_unreachable_blocks(func);
+        changed |= func.basic_blocks.len() != num_blocks;
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+pub fn referenced_labels(func: &Function) -> HashSet<SymbolId> {
+    func.basic_blocks
+        .iter()
+        .flat_map(|block| &block.instructions)
+        .flat_map(|inst| inst.operands())
+        .filter_map(|operand| match operand {
+            Value::Label(label) => Some(*label),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn label_of(block: &BasicBlock) -> Option<SymbolId> {
+    match block.instructions.first() {
+        Some(Instruction::Label(label)) => Some(*label),
+        _ => None,
+    }
+}
+
+pub fn falls_through(block: &BasicBlock) -> bool {
+    !matches!(
+        block.instructions.last(),
+        Some(
+            Instruction::Branch(_)
+                | Instruction::Return
+                | Instruction::ReturnValue(_)
+                | Instruction::TailCall { .. }
+        )
+    )
+}
+
+fn falls_through_unconditionally(block: &BasicBlock) -> bool {
+    falls_through(block)
+        && !matches!(
+            block.instructions.last(),
+            Some(Instruction::BranchCond { .. })
+        )
+}
+
+fn thread_jumps(func: &mut Function) -> bool {
+    let forwards: HashMap<SymbolId, SymbolId> = func
+        .basic_blocks
+        .iter()
+        .filter_map(|block| match block.instructions.as_slice() {
+            [Instruction::Label(label), Instruction::Branch(target)] => Some((*label, *target)),
+            _ => None,
+        })
+        .collect();
+
+    // a cycle of forwarding blocks is an infinite loop and is left alone
+    let resolve = |label: SymbolId| {
+        let mut visited = HashSet::from([label]);
+        let mut target = label;
+        while let Some(&next) = forwards.get(&target) {
+            if !visited.insert(next) {
+                return label;
+            }
+            target = next;
+        }
+        target
+    };
+
+    let mut changed = false;
+
+    for i in 0..func.basic_blocks.len() {
+        // a block falling into a forwarding block jumps straight to its target
+        if i + 1 < func.basic_blocks.len()
+            && falls_through_unconditionally(&func.basic_blocks[i])
+            && let Some(next) = label_of(&func.basic_blocks[i + 1])
+            && forwards.contains_key(&next)
+        {
+            let target = resolve(next);
+            if target != next {
+                func.basic_blocks[i]
+                    .instructions
+                    .push(Instruction::Branch(target));
+                changed = true;
+            }
+        }
+
+        if let Some(Instruction::Branch(label) | Instruction::BranchCond { label, .. }) =
+            func.basic_blocks[i].instructions.last_mut()
+        {
+            let target = resolve(*label);
+            if target != *label {
+                *label = target;
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+fn remove_redundant_branches(func: &mut Function) -> bool {
+    let mut changed = false;
+
+    for i in 1..func.basic_blocks.len() {
+        let Some(next) = label_of(&func.basic_blocks[i]) else {
+            continue;
+        };
+
+        let block = &mut func.basic_blocks[i - 1];
+        if let Some(Instruction::Branch(label) | Instruction::BranchCond { label, .. }) =
+            block.instructions.last()
+            && *label == next
+        {
+            block.instructions.pop();
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn merge_blocks(func: &mut Function) -> bool {
+    let referenced = referenced_labels(func);
+    let ids: HashMap<SymbolId, usize> = func
+        .basic_blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, block)| label_of(block).map(|label| (label, i)))
+        .collect();
+
+    for pred in 0..func.basic_blocks.len() {
+        let block = &func.basic_blocks[pred];
+        let succ = match block.instructions.last() {
+            Some(Instruction::Branch(label)) => ids[label],
+            _ if falls_through_unconditionally(block) && pred + 1 < func.basic_blocks.len() => {
+                pred + 1
+            }
+            _ => continue,
+        };
+
+        let succ_block = &func.basic_blocks[succ];
+        if succ == 0
+            || succ == pred
+            || func.cfg.predecessors[succ] != [BlockId(pred)]
+            || label_of(succ_block).is_some_and(|label| referenced.contains(&label))
+        {
+            continue;
+        }
+
+        // moving a block away from its position must not change where it
+        // falls through to
+        if succ != pred + 1 && falls_through(succ_block) {
+            continue;
+        }
+
+        let instructions: Vec<Instruction> = func.basic_blocks[succ]
+            .instructions
+            .drain(..)
+            .filter(|inst| !matches!(inst, Instruction::Label(_)))
+            .collect();
+
+        let block = &mut func.basic_blocks[pred];
+        if let Some(Instruction::Branch(_)) = block.instructions.last() {
+            block.instructions.pop();
+        }
+        block.instructions.extend(instructions);
+
+        return true;
+    }
+
+    false
+}
+
+// Drops the blocks emptied by the other steps and renumbers the rest.
+fn rebuild(func: &mut Function) {
+    func.basic_blocks
+        .retain(|block| !block.instructions.is_empty());
+    for (i, block) in func.basic_blocks.iter_mut().enumerate() {
+        block.id = BlockId(i);
+    }
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{count, parse, run, transform};
+
+    fn simplified(source: &str, inputs: &[&[i64]]) -> Program {
+        transform(source, inputs, |func, _| simplify_cfg(func))
+    }
+
+    fn is_branch(inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Branch(_) | Instruction::BranchCond { .. }
+        )
+    }
+
+    #[test]
+    fn threads_jumps_through_forwarding_blocks() {
+        let prog = simplified(
+            r"
+define @main() {
+    %c <- call input()
+    br %c :first
+    call print(3)
+    return
+    :first
+    br :second
+    :second
+    br :third
+    :third
+    call print(5)
+    return
+}
+",
+            &[&[0], &[1]],
+        );
+        let jumps = |inst: &Instruction| matches!(inst, Instruction::Branch(_));
+        assert_eq!(run(&prog, &[0]).count(jumps), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn removes_branches_to_the_next_block() {
+        let prog = simplified(
+            r"
+define @main() {
+    %c <- call input()
+    br %c :next
+    :next
+    call print(%c)
+    br :end
+    :end
+    return
+}
+",
+            &[&[0], &[1]],
+        );
+        assert_eq!(count(&prog, is_branch), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn merges_blocks_with_a_single_predecessor() {
+        let prog = simplified(
+            r"
+define @main() {
+    %x <- 3
+    br :later
+    :back
+    call print(%x)
+    return
+    :later
+    %x <- %x + 2
+    br :back
+}
+",
+            &[&[]],
+        );
+        assert_eq!(prog.functions[0].basic_blocks.len(), 1, "\n{}", prog);
+        assert_eq!(count(&prog, is_branch), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn leaves_forwarding_cycles_alone() {
+        let mut prog = parse(
+            r"
+define @main() {
+    br :spin
+    :spin
+    br :spin
+}
+",
+        );
+        simplify_cfg(&mut prog.functions[0]);
+
+        let spins = prog.functions[0].basic_blocks.iter().any(|block| {
+            matches!(
+                block.instructions.as_slice(),
+                [Instruction::Label(label), Instruction::Branch(target)] if label == target
+            )
+        });
+        assert!(spins, "\n{}", prog);
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::{HashMap, HashSet};
+
+use l3::*;
+use utils::{Interner, Loop};
+
+use crate::analysis::{compute_dominators, compute_loops};
+use crate::globalization::fresh_symbol;
+use crate::optimization::licm::{header_label, insert_preheader};
+
+// offset + base * factor, where base is a basic induction variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Derived {
+    base: SymbolId,
+    factor: i64,
+    offset: Option<Value>,
+}
+
+struct LoopInfo<'a> {
+    loop_: &'a Loop<BlockId>,
+    def_blocks: HashMap<SymbolId, BlockId>,
+}
+
+impl LoopInfo<'_> {
+    fn is_invariant(&self, val: &Value) -> bool {
+        match val {
+            Value::Variable(var) => self
+                .def_blocks
+                .get(var)
+                .is_none_or(|&block| !self.loop_.contains(block)),
+            _ => true,
+        }
+    }
+}
+
+pub fn reduce_strength(func: &mut Function, interner: &mut Interner<String>) {
+    let mut visited = HashSet::new();
+
+    loop {
+        let dt = compute_dominators(func);
+        let loops = compute_loops(func, &dt);
+
+        let Some(loop_) = loops
+            .loops()
+            .iter()
+            .find(|loop_| !visited.contains(&header_label(func, loop_.header())))
+        else {
+            break;
+        };
+        let label = header_label(func, loop_.header());
+
+        let info = LoopInfo {
+            loop_,
+            def_blocks: func
+                .basic_blocks
+                .iter()
+                .flat_map(|block| {
+                    block
+                        .instructions
+                        .iter()
+                        .filter_map(move |inst| inst.defs().map(|def| (def, block.id)))
+                })
+                .collect(),
+        };
+
+        let inductions = find_inductions(func, &info);
+        let sites = find_derived(func, &info, &inductions);
+        if sites.is_empty() {
+            visited.insert(label);
+            continue;
+        }
+
+        // the loop is revisited once it has a preheader to initialize the new variables in
+        match find_preheader(func, loop_) {
+            Some(preheader) => {
+                rewrite_loop(func, &info, preheader, &inductions, &sites, interner);
+                visited.insert(label);
+            }
+            None => {
+                if insert_preheader(func, loop_, Vec::new(), interner).is_none() {
+                    visited.insert(label);
+                }
+            }
+        }
+    }
+}
+
+fn find_inductions(func: &Function, info: &LoopInfo) -> HashMap<SymbolId, i64> {
+    let defs: HashMap<SymbolId, &Instruction> = info
+        .loop_
+        .basic_blocks()
+        .iter()
+        .flat_map(|id| &func.basic_blocks[id.0].instructions)
+        .filter_map(|inst| inst.defs().map(|def| (def, inst)))
+        .collect();
+
+    func.basic_blocks[info.loop_.header().0]
+        .instructions
+        .iter()
+        .filter_map(|inst| {
+            let Instruction::Phi { dst, args } = inst else {
+                return None;
+            };
+
+            let mut steps = args
+                .iter()
+                .filter(|(pred, _)| info.loop_.contains(*pred))
+                .map(|(_, arg)| match arg {
+                    Value::Variable(var) => defs.get(var).and_then(|inst| step(inst, *dst)),
+                    _ => None,
+                });
+
+            let first = steps.next()??;
+            steps
+                .all(|step| step == Some(first))
+                .then_some((*dst, first))
+        })
+        .collect()
+}
+
+fn step(inst: &Instruction, var: SymbolId) -> Option<i64> {
+    match inst {
+        Instruction::Binary {
+            lhs: Value::Variable(lhs),
+            op: BinaryOp::Add,
+            rhs: Value::Number(step),
+            ..
+        }
+        | Instruction::Binary {
+            lhs: Value::Number(step),
+            op: BinaryOp::Add,
+            rhs: Value::Variable(lhs),
+            ..
+        } if *lhs == var => Some(*step),
+
+        Instruction::Binary {
+            lhs: Value::Variable(lhs),
+            op: BinaryOp::Sub,
+            rhs: Value::Number(step),
+            ..
+        } if *lhs == var => Some(step.wrapping_neg()),
+
+        _ => None,
+    }
+}
+
+fn find_derived(
+    func: &Function,
+    info: &LoopInfo,
+    inductions: &HashMap<SymbolId, i64>,
+) -> Vec<(BlockId, usize, Derived)> {
+    let mut derived: HashMap<SymbolId, Derived> = HashMap::new();
+    let mut sites = Vec::new();
+
+    loop {
+        let mut changed = false;
+
+        for &id in info.loop_.basic_blocks() {
+            for (i, inst) in func.basic_blocks[id.0].instructions.iter().enumerate() {
+                let Instruction::Binary { dst, lhs, op, rhs } = inst else {
+                    continue;
+                };
+                if derived.contains_key(dst) {
+                    continue;
+                }
+
+                let found = match (lhs, op, rhs) {
+                    (Value::Variable(var), BinaryOp::Mul, Value::Number(factor))
+                    | (Value::Number(factor), BinaryOp::Mul, Value::Variable(var))
+                        if inductions.contains_key(var) =>
+                    {
+                        Some(Derived {
+                            base: *var,
+                            factor: *factor,
+                            offset: None,
+                        })
+                    }
+
+                    (Value::Variable(var), BinaryOp::Shl, Value::Number(shift))
+                        if inductions.contains_key(var) && (0..63).contains(shift) =>
+                    {
+                        Some(Derived {
+                            base: *var,
+                            factor: 1 << shift,
+                            offset: None,
+                        })
+                    }
+
+                    (Value::Variable(var), BinaryOp::Add, other)
+                    | (other, BinaryOp::Add, Value::Variable(var))
+                        if derived.get(var).is_some_and(|d| d.offset.is_none())
+                            && info.is_invariant(other) =>
+                    {
+                        Some(Derived {
+                            offset: Some(*other),
+                            ..derived[var]
+                        })
+                    }
+
+                    _ => None,
+                };
+
+                if let Some(found) = found {
+                    derived.insert(*dst, found);
+                    sites.push((id, i, found));
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    sites
+}
+
+fn find_preheader(func: &Function, loop_: &Loop<BlockId>) -> Option<BlockId> {
+    let header = loop_.header();
+    let outside_preds: Vec<BlockId> = func.cfg.predecessors[header.0]
+        .iter()
+        .copied()
+        .filter(|&pred| !loop_.contains(pred))
+        .collect();
+
+    match outside_preds[..] {
+        [pred] if func.cfg.successors[pred.0] == [header] => Some(pred),
+        _ => None,
+    }
+}
+
+fn rewrite_loop(
+    func: &mut Function,
+    info: &LoopInfo,
+    preheader: BlockId,
+    inductions: &HashMap<SymbolId, i64>,
+    sites: &[(BlockId, usize, Derived)],
+    interner: &mut Interner<String>,
+) {
+    let header = info.loop_.header();
+    let latches: Vec<BlockId> = func.cfg.predecessors[header.0]
+        .iter()
+        .copied()
+        .filter(|&pred| info.loop_.contains(pred))
+        .collect();
+
+    let initial_values: HashMap<SymbolId, Value> = func.basic_blocks[header.0]
+        .instructions
+        .iter()
+        .filter_map(|inst| match inst {
+            Instruction::Phi { dst, args } => args
+                .iter()
+                .find(|(pred, _)| *pred == preheader)
+                .map(|&(_, arg)| (*dst, arg)),
+            _ => None,
+        })
+        .collect();
+
+    let mut reduced: Vec<(Derived, SymbolId)> = Vec::new();
+    let mut init = Vec::new();
+    let mut phis = Vec::new();
+    let mut updates = Vec::new();
+
+    for &(id, i, derived) in sites {
+        let dst = func.basic_blocks[id.0].instructions[i].defs().unwrap();
+
+        let var = match reduced.iter().find(|(other, _)| *other == derived) {
+            Some(&(_, var)) => var,
+            None => {
+                let base_name = interner.resolve(dst.0).clone();
+                let var = fresh_symbol(interner, &base_name);
+
+                let first = scale(
+                    initial_values[&derived.base],
+                    derived,
+                    &base_name,
+                    &mut init,
+                    interner,
+                );
+
+                let step = inductions[&derived.base].wrapping_mul(derived.factor);
+                let mut args = vec![(preheader, Value::Variable(first))];
+                for &latch in &latches {
+                    let next = fresh_symbol(interner, &base_name);
+                    updates.push((
+                        latch,
+                        Instruction::Binary {
+                            dst: next,
+                            lhs: Value::Variable(var),
+                            op: BinaryOp::Add,
+                            rhs: Value::Number(step),
+                        },
+                    ));
+                    args.push((latch, Value::Variable(next)));
+                }
+
+                phis.push(Instruction::Phi { dst: var, args });
+                reduced.push((derived, var));
+                var
+            }
+        };
+
+        func.basic_blocks[id.0].instructions[i] = Instruction::Assign {
+            dst,
+            src: Value::Variable(var),
+        };
+    }
+
+    let mut replaced = HashSet::new();
+    for &(derived, var) in &reduced {
+        if derived.factor > 0 && replaced.insert(derived.base) {
+            let step = inductions[&derived.base];
+            replace_exit_test(func, info, derived, var, step, &mut init, interner);
+        }
+    }
+
+    let header_insts = &mut func.basic_blocks[header.0].instructions;
+    let num_phis = header_insts
+        .iter()
+        .skip(1)
+        .take_while(|inst| matches!(inst, Instruction::Phi { .. }))
+        .count();
+    header_insts.splice(1 + num_phis..1 + num_phis, phis);
+
+    insert_before_terminator(&mut func.basic_blocks[preheader.0], init);
+    for (latch, update) in updates {
+        insert_before_terminator(&mut func.basic_blocks[latch.0], vec![update]);
+    }
+}
+
+// emits offset + val * factor and returns the variable holding it
+fn scale(
+    val: Value,
+    derived: Derived,
+    name: &str,
+    insts: &mut Vec<Instruction>,
+    interner: &mut Interner<String>,
+) -> SymbolId {
+    let scaled = fresh_symbol(interner, name);
+    insts.push(Instruction::Binary {
+        dst: scaled,
+        lhs: val,
+        op: BinaryOp::Mul,
+        rhs: Value::Number(derived.factor),
+    });
+
+    match derived.offset {
+        Some(offset) => {
+            let sum = fresh_symbol(interner, name);
+            insts.push(Instruction::Binary {
+                dst: sum,
+                lhs: Value::Variable(scaled),
+                op: BinaryOp::Add,
+                rhs: offset,
+            });
+            sum
+        }
+        None => scaled,
+    }
+}
+
+// When the basic induction variable only feeds its own update and a single
+// comparison against an invariant bound, compare the reduced variable against
+// the scaled bound instead so that the counter dies. Scaling only keeps the
+// order of the compared values if none of them overflows, so this is limited
+// to constant ends that provably stay in range.
+fn replace_exit_test(
+    func: &mut Function,
+    info: &LoopInfo,
+    derived: Derived,
+    var: SymbolId,
+    step: i64,
+    init: &mut Vec<Instruction>,
+    interner: &mut Interner<String>,
+) {
+    let base = derived.base;
+    let mut users: HashMap<SymbolId, Vec<(BlockId, usize)>> = HashMap::new();
+    for block in &func.basic_blocks {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            for use_ in inst.uses() {
+                users.entry(use_).or_default().push((block.id, i));
+            }
+        }
+    }
+
+    let header = &func.basic_blocks[info.loop_.header().0];
+    let Some((initial, updates)) = header.instructions.iter().find_map(|inst| match inst {
+        Instruction::Phi { dst, args } if *dst == base => {
+            let (inside, outside): (Vec<_>, Vec<_>) = args
+                .iter()
+                .partition(|(pred, _)| info.loop_.contains(*pred));
+            let updates: Vec<SymbolId> = inside
+                .iter()
+                .filter_map(|(_, arg)| match arg {
+                    Value::Variable(var) => Some(*var),
+                    _ => None,
+                })
+                .collect();
+            match outside[..] {
+                [(_, initial)] => Some((initial, updates)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }) else {
+        return;
+    };
+
+    // each update may only flow back into the header
+    if updates.iter().any(|update| {
+        users
+            .get(update)
+            .is_some_and(|uses| uses.iter().any(|&(id, _)| id != header.id))
+    }) {
+        return;
+    }
+
+    let mut compares = users.get(&base).into_iter().flatten().filter(|&&(id, i)| {
+        func.basic_blocks[id.0].instructions[i]
+            .defs()
+            .is_none_or(|def| !updates.contains(&def))
+    });
+    let (Some(&(id, i)), None) = (compares.next(), compares.next()) else {
+        return;
+    };
+
+    let Instruction::Compare { dst, lhs, cmp, rhs } =
+        func.basic_blocks[id.0].instructions[i].clone()
+    else {
+        return;
+    };
+    if !info.loop_.contains(id) {
+        return;
+    }
+
+    let (bound, bound_on_right) = match (lhs, rhs) {
+        (Value::Variable(var), bound) if var == base && info.is_invariant(&bound) => (bound, true),
+        (bound, Value::Variable(var)) if var == base && info.is_invariant(&bound) => (bound, false),
+        _ => return,
+    };
+
+    // the counter moves from its initial value toward the bound and passes it
+    // by less than a step before the compare sends it out of the loop
+    let (Value::Number(first), Value::Number(last)) = (initial, bound) else {
+        return;
+    };
+    let toward = if step > 0 {
+        first <= last
+    } else {
+        first >= last
+    };
+    let past = last.checked_add(step - step.signum());
+    let scaled = |val: i64| {
+        let product = val.checked_mul(derived.factor)?;
+        match derived.offset {
+            None => Some(product),
+            Some(Value::Number(offset)) => product.checked_add(offset),
+            Some(_) => None,
+        }
+    };
+    if !toward || past.and_then(scaled).is_none() || scaled(first).is_none() {
+        return;
+    }
+
+    let name = interner.resolve(dst.0).clone();
+    let limit = Value::Variable(scale(bound, derived, &name, init, interner));
+    let reduced = Value::Variable(var);
+
+    let (lhs, rhs) = if bound_on_right {
+        (reduced, limit)
+    } else {
+        (limit, reduced)
+    };
+    func.basic_blocks[id.0].instructions[i] = Instruction::Compare { dst, lhs, cmp, rhs };
+}
+
+fn insert_before_terminator(block: &mut BasicBlock, insts: Vec<Instruction>) {
+    let position = match block.instructions.last() {
+        Some(Instruction::Branch(_) | Instruction::BranchCond { .. }) => {
+            block.instructions.len() - 1
+        }
+        _ => block.instructions.len(),
+    };
+    block.instructions.splice(position..position, insts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimization::propagate_constants;
+    use crate::ssa::construct_ssa;
+    use crate::testing::{count, run, transform};
+
+    // Sums the first %n words of an array, where %n is the input.
+    const SUM: &str = r"
+define @main() {
+    %n <- call input()
+    %p <- call allocate(%n, 5)
+    %n <- %n >> 1
+    %i <- 0
+    %s <- 1
+    :loop
+    %c <- %i < %n
+    br %c :body
+    call print(%s)
+    return
+    :body
+    %o <- %i * 8
+    %o <- %o + 8
+    %a <- %p + %o
+    %v <- load %a
+    %s <- %s + %v
+    %s <- %s - 1
+    %i <- %i + 1
+    br :loop
+}
+";
+
+    const INPUTS: [&[i64]; 2] = [&[2], &[6]];
+
+    fn reduced(source: &str) -> Program {
+        transform(source, &INPUTS, |func, interner| {
+            construct_ssa(func, interner);
+            propagate_constants(func, interner);
+            reduce_strength(func, interner);
+        })
+    }
+
+    fn is_mul(inst: &Instruction) -> bool {
+        matches!(
+            inst,
+            Instruction::Binary {
+                op: BinaryOp::Mul,
+                ..
+            }
+        )
+    }
+
+    #[test]
+    fn replaces_multiplications_by_induction_variables() {
+        let prog = reduced(SUM);
+        let short = run(&prog, &[2]).count(is_mul);
+        let long = run(&prog, &[6]).count(is_mul);
+        assert_eq!(short, long, "\n{}", prog);
+    }
+
+    #[test]
+    fn compares_the_reduced_variable_on_exit() {
+        let prog = reduced(&SUM.replace("%i < %n", "%i < 2"));
+        let reads_i = |inst: &Instruction| {
+            matches!(inst, Instruction::Compare { .. })
+                && inst
+                    .uses()
+                    .iter()
+                    .any(|var| prog.interner.resolve(var.0).starts_with('i'))
+        };
+        assert_eq!(count(&prog, reads_i), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn keeps_exit_tests_that_could_overflow_once_scaled() {
+        for bound in ["1152921504606846976", "%n"] {
+            let source = format!(
+                r"
+define @main() {{
+    %i <- 1152921504606846974
+    %n <- 1152921504606846976
+    %k <- 1
+    :loop
+    %c <- %i < {}
+    br %c :body
+    call print(%k)
+    return
+    :body
+    %o <- %i * 8
+    %k <- %k + 2
+    %i <- %i + 1
+    br :loop
+}}
+",
+                bound
+            );
+            let prog = reduced(&source);
+            assert_eq!(run(&prog, &[]).output, ["2"], "\n{}", prog);
+        }
+    }
+
+    #[test]
+    fn leaves_nonlinear_updates_alone() {
+        let source = SUM
+            .replace("%i <- 0", "%i <- 1")
+            .replace("%i <- %i + 1", "%i <- %i << 1");
+        let prog = reduced(&source);
+        let short = run(&prog, &[2]).count(is_mul);
+        let long = run(&prog, &[6]).count(is_mul);
+        assert!(short < long, "\n{}", prog);
+    }
+}
@@ -0,0 +1,136 @@
+use l3::*;
+
+use crate::translation::ARG_REGISTERS;
+
+pub fn mark_tail_calls(func: &mut Function) {
+    let num_stack_params = func.params.len().saturating_sub(ARG_REGISTERS.len());
+
+    for block in &mut func.basic_blocks {
+        let [.., call, ret] = block.instructions.as_slice() else {
+            continue;
+        };
+
+        let is_tail_call = match (call, ret) {
+            (
+                Instruction::CallResult {
+                    dst,
+                    callee: Callee::Value(_),
+                    args,
+                },
+                Instruction::ReturnValue(Value::Variable(result)),
+            ) => dst == result && fits_in_frame(args, num_stack_params),
+            (
+                Instruction::Call {
+                    callee: Callee::Value(_),
+                    args,
+                },
+                Instruction::Return,
+            ) => fits_in_frame(args, num_stack_params),
+            _ => false,
+        };
+
+        if is_tail_call {
+            block.instructions.pop();
+            if let Some(
+                Instruction::Call { callee, args } | Instruction::CallResult { callee, args, .. },
+            ) = block.instructions.pop()
+            {
+                block
+                    .instructions
+                    .push(Instruction::TailCall { callee, args });
+            }
+        }
+    }
+}
+
+// the callee's stack arguments are written over our own incoming ones
+fn fits_in_frame(args: &[Value], num_stack_params: usize) -> bool {
+    args.len().saturating_sub(ARG_REGISTERS.len()) <= num_stack_params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{symbol, transform};
+
+    fn marked(source: &str) -> Program {
+        transform(source, &[&[]], |func, _| mark_tail_calls(func))
+    }
+
+    fn tail_calls(prog: &Program, name: &str) -> usize {
+        let name = symbol(prog, name);
+        prog.functions
+            .iter()
+            .filter(|func| func.name == name)
+            .flat_map(|func| &func.basic_blocks)
+            .flat_map(|block| &block.instructions)
+            .filter(|inst| matches!(inst, Instruction::TailCall { .. }))
+            .count()
+    }
+
+    #[test]
+    fn marks_calls_whose_result_is_returned() {
+        let prog = marked(
+            r"
+define @main() {
+    %x <- call @f(5)
+    call print(%x)
+    return
+}
+
+define @f(%n) {
+    %r <- call @g(%n)
+    return %r
+}
+
+define @g(%n) {
+    %r <- call @h(%n)
+    %r <- %r + 2
+    return %r
+}
+
+define @h(%n) {
+    call print(%n)
+    return %n
+}
+",
+        );
+        assert_eq!(tail_calls(&prog, "f"), 1, "\n{}", prog);
+        for name in ["main", "g", "h"] {
+            assert_eq!(tail_calls(&prog, name), 0, "\n{}", prog);
+        }
+    }
+
+    #[test]
+    fn needs_room_for_stack_arguments() {
+        let prog = marked(
+            r"
+define @main() {
+    %x <- call @few(1)
+    %y <- call @many(1, 3, 5, 7, 9, 11, 13, 15)
+    %x <- %x + %y
+    %x <- %x - 1
+    call print(%x)
+    return
+}
+
+define @few(%a) {
+    %r <- call @sum(%a, 3, 5, 7, 9, 11, 13, 15)
+    return %r
+}
+
+define @many(%a, %b, %c, %d, %e, %f, %g, %h) {
+    %r <- call @sum(%h, %g, %f, %e, %d, %c, %b, %a)
+    return %r
+}
+
+define @sum(%a, %b, %c, %d, %e, %f, %g, %h) {
+    %a <- %a + %h
+    return %a
+}
+",
+        );
+        assert_eq!(tail_calls(&prog, "few"), 0, "\n{}", prog);
+        assert_eq!(tail_calls(&prog, "many"), 1, "\n{}", prog);
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::DominatorTree;
+
+use crate::analysis::compute_dominators;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Expression<T> {
+    Binary(BinaryOp, T, T),
+    Compare(CompareOp, T, T),
+    Load(T),
+}
+
+fn expressions<T: Copy>(
+    inst: &Instruction,
+    mut operand: impl FnMut(&Value) -> T,
+) -> Vec<Expression<T>> {
+    match inst {
+        Instruction::Binary { lhs, op, rhs, .. } => {
+            let (lhs, rhs) = (operand(lhs), operand(rhs));
+            let mut exprs = vec![Expression::Binary(op.clone(), lhs, rhs)];
+            if matches!(op, BinaryOp::Add | BinaryOp::Mul | BinaryOp::BitAnd) {
+                exprs.push(Expression::Binary(op.clone(), rhs, lhs));
+            }
+            exprs
+        }
+
+        Instruction::Compare { lhs, cmp, rhs, .. } => {
+            let (lhs, rhs) = (operand(lhs), operand(rhs));
+            match cmp {
+                CompareOp::Gt => vec![Expression::Compare(CompareOp::Lt, rhs, lhs)],
+                CompareOp::Ge => vec![Expression::Compare(CompareOp::Le, rhs, lhs)],
+                CompareOp::Eq => vec![
+                    Expression::Compare(CompareOp::Eq, lhs, rhs),
+                    Expression::Compare(CompareOp::Eq, rhs, lhs),
+                ],
+                cmp => vec![Expression::Compare(cmp.clone(), lhs, rhs)],
+            }
+        }
+
+        Instruction::Load { src, .. } => vec![Expression::Load(operand(&Value::Variable(*src)))],
+
+        _ => Vec::new(),
+    }
+}
+
+fn clobbers_memory(inst: &Instruction) -> bool {
+    matches!(
+        inst,
+        Instruction::Store { .. }
+            | Instruction::Call { .. }
+            | Instruction::CallResult { .. }
+            | Instruction::TailCall { .. }
+    )
+}
+
+#[derive(Debug, Default)]
+struct LocalNumbering {
+    numbers: HashMap<Value, usize>,
+    table: HashMap<Expression<usize>, (usize, SymbolId)>,
+    next: usize,
+}
+
+impl LocalNumbering {
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    fn number(&mut self, val: &Value) -> usize {
+        match self.numbers.get(val) {
+            Some(&number) => number,
+            None => {
+                let number = self.fresh();
+                self.numbers.insert(*val, number);
+                number
+            }
+        }
+    }
+
+    fn holder(&self, expr: &Expression<usize>) -> Option<(usize, SymbolId)> {
+        self.table
+            .get(expr)
+            .copied()
+            .filter(|(number, holder)| self.numbers.get(&Value::Variable(*holder)) == Some(number))
+    }
+}
+
+pub fn number_values_locally(func: &mut Function) {
+    for block in &mut func.basic_blocks {
+        let mut lvn = LocalNumbering::default();
+
+        for inst in &mut block.instructions {
+            if clobbers_memory(inst) {
+                lvn.table
+                    .retain(|expr, _| !matches!(expr, Expression::Load(_)));
+            }
+
+            let Some(dst) = inst.defs() else {
+                continue;
+            };
+
+            let exprs = expressions(inst, |val| lvn.number(val));
+
+            let number = if let Instruction::Assign { src, .. } = inst {
+                lvn.number(src)
+            } else if let Some((number, holder)) = exprs.iter().find_map(|expr| lvn.holder(expr)) {
+                *inst = Instruction::Assign {
+                    dst,
+                    src: Value::Variable(holder),
+                };
+                number
+            } else {
+                let number = lvn.fresh();
+                if let Some(expr) = exprs.into_iter().next() {
+                    lvn.table.insert(expr, (number, dst));
+                }
+                number
+            };
+
+            lvn.numbers.insert(Value::Variable(dst), number);
+        }
+    }
+}
+
+pub fn number_values_globally(func: &mut Function) {
+    let dom_tree = compute_dominators(func);
+    let mut available = HashMap::new();
+    let mut replacements = HashMap::new();
+
+    number_block(
+        func,
+        BlockId(0),
+        &dom_tree,
+        &mut available,
+        &mut replacements,
+    );
+
+    for inst in func
+        .basic_blocks
+        .iter_mut()
+        .flat_map(|block| &mut block.instructions)
+    {
+        for use_ in inst.uses_mut() {
+            if let Some(&replacement) = replacements.get(use_) {
+                *use_ = replacement;
+            }
+        }
+    }
+}
+
+fn number_block(
+    func: &mut Function,
+    id: BlockId,
+    dom_tree: &DominatorTree<BlockId>,
+    available: &mut HashMap<Expression<Value>, SymbolId>,
+    replacements: &mut HashMap<SymbolId, SymbolId>,
+) {
+    let mut inserted = Vec::new();
+    let mut loads = HashMap::new();
+
+    for inst in &mut func.basic_blocks[id.0].instructions {
+        for use_ in inst.uses_mut() {
+            if let Some(&replacement) = replacements.get(use_) {
+                *use_ = replacement;
+            }
+        }
+
+        if clobbers_memory(inst) {
+            loads.clear();
+        }
+
+        let exprs = expressions(inst, |val| *val);
+        let Some(dst) = inst.defs().filter(|_| !exprs.is_empty()) else {
+            continue;
+        };
+
+        let table = if let Instruction::Load { .. } = inst {
+            &mut loads
+        } else {
+            &mut *available
+        };
+
+        if let Some(&holder) = exprs.iter().find_map(|expr| table.get(expr)) {
+            replacements.insert(dst, holder);
+            *inst = Instruction::Assign {
+                dst,
+                src: Value::Variable(holder),
+            };
+        } else {
+            let expr = exprs.into_iter().next().unwrap();
+            if !matches!(expr, Expression::Load(_)) {
+                inserted.push(expr.clone());
+            }
+            table.insert(expr, dst);
+        }
+    }
+
+    for &child in dom_tree.children(id) {
+        number_block(func, child, dom_tree, available, replacements);
+    }
+
+    for expr in inserted {
+        available.remove(&expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssa::construct_ssa;
+    use crate::testing::{count, def, symbol, transform};
+
+    fn copies(prog: &Program, dst: &str, src: &str) -> bool {
+        *def(prog, dst)
+            == Instruction::Assign {
+                dst: symbol(prog, dst),
+                src: Value::Variable(symbol(prog, src)),
+            }
+    }
+
+    fn adds(num: i64) -> impl Fn(&Instruction) -> bool {
+        move |inst| match inst {
+            Instruction::Binary {
+                op: BinaryOp::Add,
+                lhs,
+                rhs,
+                ..
+            } => *lhs == Value::Number(num) || *rhs == Value::Number(num),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn reuses_commuted_expressions_locally() {
+        let prog = transform(
+            r"
+define @main() {
+    %x <- call input()
+    %y <- call input()
+    %a <- %x + %y
+    %b <- %y + %x
+    %c <- %x < %y
+    %d <- %y > %x
+    %a <- %a + %b
+    %a <- %a + %c
+    %a <- %a + %d
+    %a <- %a << 1
+    %a <- %a + 1
+    call print(%a)
+    return
+}
+",
+            &[&[2, 3], &[3, 2]],
+            |func, _| number_values_locally(func),
+        );
+        assert!(copies(&prog, "b", "a"), "\n{}", prog);
+        assert!(copies(&prog, "d", "c"), "\n{}", prog);
+    }
+
+    #[test]
+    fn forgets_loads_after_stores() {
+        let prog = transform(
+            r"
+define @main() {
+    %p <- call allocate(3, 3)
+    %a <- load %p
+    store %p <- 5
+    %b <- load %p
+    %c <- load %p
+    %a <- %a + %b
+    %a <- %a + %c
+    call print(%a)
+    return
+}
+",
+            &[&[]],
+            |func, _| number_values_locally(func),
+        );
+        assert!(
+            matches!(def(&prog, "b"), Instruction::Load { .. }),
+            "\n{}",
+            prog
+        );
+        assert!(copies(&prog, "c", "b"), "\n{}", prog);
+    }
+
+    #[test]
+    fn reuses_expressions_from_dominators() {
+        let prog = transform(
+            r"
+define @main() {
+    %x <- call input()
+    %a <- %x + 2
+    %c <- %x < 9
+    br %c :small
+    %b <- %x + 2
+    %d <- %x + 4
+    br :join
+    :small
+    %b <- 2 + %x
+    %d <- %x + 4
+    :join
+    %e <- %x + 4
+    %b <- %b + %d
+    %b <- %b + %e
+    %b <- %b + %a
+    %b <- %b << 1
+    %b <- %b + 1
+    call print(%b)
+    return
+}
+",
+            &[&[3], &[12]],
+            |func, interner| {
+                construct_ssa(func, interner);
+                number_values_globally(func);
+            },
+        );
+        // both branches reuse the entry block's sum, but neither dominates the
+        // join, so each computes its own
+        assert_eq!(count(&prog, adds(2)), 1, "\n{}", prog);
+        assert_eq!(count(&prog, adds(4)), 3, "\n{}", prog);
+    }
+}
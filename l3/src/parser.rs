@@ -9,11 +9,15 @@ use utils::Interner;
 type MyExtra<'src> = extra::Full<Rich<'src, char>, extra::SimpleState<Interner<String>>, ()>;
 
 pub fn parse_file(file_name: &str) -> Option<Program> {
+    let input = fs::read_to_string(file_name).unwrap_or_else(|e| panic!("{}", e));
+    parse_source(file_name, &input)
+}
+
+pub fn parse_source(file_name: &str, input: &str) -> Option<Program> {
     let file_name = file_name.to_string();
-    let input = fs::read_to_string(&file_name).unwrap_or_else(|e| panic!("{}", e));
 
     let (output, errors) = program()
-        .parse_with_state(&input, &mut extra::SimpleState(Interner::new()))
+        .parse_with_state(input, &mut extra::SimpleState(Interner::new()))
         .into_output_errors();
 
     errors.into_iter().for_each(|e| {
@@ -29,7 +33,7 @@ pub fn parse_file(file_name: &str) -> Option<Program> {
                 .with_color(Color::Red),
         )
         .finish()
-        .eprint(sources([(file_name.clone(), input.clone())]))
+        .eprint(sources([(file_name.clone(), input.to_string())]))
         .unwrap();
     });
 
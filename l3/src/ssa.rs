@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::{BitVector, DominatorTree, Interner};
+
+use crate::analysis::{LivenessResult, compute_dominators, compute_liveness};
+use crate::globalization::fresh_symbol;
+
+pub fn construct_ssa(func: &mut Function, interner: &mut Interner<String>) {
+    separate_entry(func, interner);
+
+    let dom_tree = compute_dominators(func);
+    let liveness = compute_liveness(func);
+
+    insert_phis(func, &dom_tree, &liveness);
+
+    let mut stacks: HashMap<SymbolId, Vec<SymbolId>> = func
+        .params
+        .iter()
+        .map(|&param| (param, vec![param]))
+        .collect();
+
+    rename_block(func, BlockId(0), &dom_tree, interner, &mut stacks);
+}
+
+// Phis in the entry block have no argument for the values coming in with the
+// call, so a loop back to the entry gets a new block in front of it.
+fn separate_entry(func: &mut Function, interner: &mut Interner<String>) {
+    if func.cfg.predecessors[0].is_empty() {
+        return;
+    }
+
+    let label = fresh_symbol(interner, "entry");
+    func.basic_blocks.insert(
+        0,
+        BasicBlock {
+            id: BlockId(0),
+            instructions: vec![Instruction::Label(label)],
+        },
+    );
+    for (i, block) in func.basic_blocks.iter_mut().enumerate() {
+        block.id = BlockId(i);
+    }
+
+    func.cfg = ControlFlowGraph::new(&func.basic_blocks);
+}
+
+fn insert_phis(func: &mut Function, dom_tree: &DominatorTree<BlockId>, liveness: &LivenessResult) {
+    let mut def_sites: HashMap<SymbolId, Vec<BlockId>> = HashMap::new();
+
+    for &param in &func.params {
+        def_sites.entry(param).or_default().push(BlockId(0));
+    }
+
+    for block in &func.basic_blocks {
+        for def in block.instructions.iter().filter_map(|inst| inst.defs()) {
+            let sites = def_sites.entry(def).or_default();
+            if sites.last() != Some(&block.id) {
+                sites.push(block.id);
+            }
+        }
+    }
+
+    let mut def_sites: Vec<(SymbolId, Vec<BlockId>)> = def_sites.into_iter().collect();
+    def_sites.sort_by_key(|(var, _)| var.0);
+
+    for (var, mut worklist) in def_sites {
+        let mut placed = BitVector::new(func.basic_blocks.len());
+
+        while let Some(id) = worklist.pop() {
+            for &frontier in dom_tree.frontier(id) {
+                if placed.test(frontier.0) || !liveness.is_live_in(frontier, var) {
+                    continue;
+                }
+
+                placed.set(frontier.0);
+                worklist.push(frontier);
+
+                let block = &mut func.basic_blocks[frontier.0];
+                let start = match block.instructions.first() {
+                    Some(Instruction::Label(_)) => 1,
+                    _ => 0,
+                };
+                let args = func.cfg.predecessors[frontier.0]
+                    .iter()
+                    .map(|&pred| (pred, Value::Variable(var)))
+                    .collect();
+
+                block
+                    .instructions
+                    .insert(start, Instruction::Phi { dst: var, args });
+            }
+        }
+    }
+}
+
+fn rename_block(
+    func: &mut Function,
+    id: BlockId,
+    dom_tree: &DominatorTree<BlockId>,
+    interner: &mut Interner<String>,
+    stacks: &mut HashMap<SymbolId, Vec<SymbolId>>,
+) {
+    let mut pushed = Vec::new();
+
+    for inst in &mut func.basic_blocks[id.0].instructions {
+        if !matches!(inst, Instruction::Phi { .. }) {
+            for use_ in inst.uses_mut() {
+                if let Some(&top) = stacks.get(use_).and_then(|stack| stack.last()) {
+                    *use_ = top;
+                }
+            }
+        }
+
+        if let Some(def) = inst.defs_mut() {
+            let base = interner.resolve(def.0).clone();
+            let name = fresh_symbol(interner, &base);
+            stacks.entry(*def).or_default().push(name);
+            pushed.push(*def);
+            *def = name;
+        }
+    }
+
+    for &succ in &func.cfg.successors[id.0] {
+        for inst in &mut func.basic_blocks[succ.0].instructions {
+            let Instruction::Phi { args, .. } = inst else {
+                continue;
+            };
+
+            for (pred, arg) in args {
+                if *pred == id
+                    && let Value::Variable(var) = arg
+                    && let Some(&top) = stacks.get(var).and_then(|stack| stack.last())
+                {
+                    *arg = Value::Variable(top);
+                }
+            }
+        }
+    }
+
+    for &child in dom_tree.children(id) {
+        rename_block(func, child, dom_tree, interner, stacks);
+    }
+
+    for var in pushed {
+        stacks.get_mut(&var).unwrap().pop();
+    }
+}
+
+pub fn destruct_ssa(func: &mut Function, interner: &mut Interner<String>) {
+    let mut copies: HashMap<(BlockId, BlockId), Vec<(SymbolId, Value)>> = HashMap::new();
+
+    for block in &mut func.basic_blocks {
+        for inst in &block.instructions {
+            if let Instruction::Phi { dst, args } = inst {
+                for &(pred, arg) in args {
+                    copies
+                        .entry((pred, block.id))
+                        .or_default()
+                        .push((*dst, arg));
+                }
+            }
+        }
+
+        block
+            .instructions
+            .retain(|inst| !matches!(inst, Instruction::Phi { .. }));
+    }
+
+    let block_ids: HashMap<SymbolId, BlockId> = func
+        .basic_blocks
+        .iter()
+        .filter_map(|block| match block.instructions.first() {
+            Some(Instruction::Label(label)) => Some((*label, block.id)),
+            _ => None,
+        })
+        .collect();
+
+    let num_blocks = func.basic_blocks.len();
+    let mut instructions = Vec::new();
+    let mut trampolines = Vec::new();
+
+    for block in &func.basic_blocks {
+        let next = BlockId(block.id.0 + 1);
+        let edge_copies = |succ: BlockId, interner: &mut Interner<String>| {
+            copies
+                .get(&(block.id, succ))
+                .map(|edge| sequentialize(edge, interner))
+                .unwrap_or_default()
+        };
+
+        let (last, rest) = block
+            .instructions
+            .split_last()
+            .expect("block should not be empty");
+        instructions.extend(rest.iter().cloned());
+
+        match last {
+            Instruction::BranchCond { cond, label } => {
+                let target = block_ids[label];
+                let mut label = *label;
+                let target_copies = edge_copies(target, interner);
+
+                if !target_copies.is_empty() {
+                    let trampoline = fresh_symbol(interner, "edge");
+                    trampolines.push(Instruction::Label(trampoline));
+                    trampolines.extend(target_copies);
+                    trampolines.push(Instruction::Branch(label));
+                    label = trampoline;
+                }
+
+                instructions.push(Instruction::BranchCond { cond: *cond, label });
+
+                if next.0 < num_blocks {
+                    instructions.extend(edge_copies(next, interner));
+                }
+            }
+
+            Instruction::Branch(label) => {
+                instructions.extend(edge_copies(block_ids[label], interner));
+                instructions.push(last.clone());
+            }
+
+            Instruction::Return | Instruction::ReturnValue(_) => instructions.push(last.clone()),
+
+            _ => {
+                instructions.push(last.clone());
+                if next.0 < num_blocks {
+                    instructions.extend(edge_copies(next, interner));
+                }
+            }
+        }
+    }
+
+    instructions.extend(trampolines);
+
+    *func = Function::new(func.name, func.params.clone(), instructions);
+}
+
+fn sequentialize(
+    copies: &[(SymbolId, Value)],
+    interner: &mut Interner<String>,
+) -> Vec<Instruction> {
+    let mut pending: Vec<(SymbolId, Value)> = copies
+        .iter()
+        .filter(|&&(dst, src)| src != Value::Variable(dst))
+        .copied()
+        .collect();
+    let mut sequence = Vec::new();
+
+    while !pending.is_empty() {
+        let ready = pending
+            .iter()
+            .position(|&(dst, _)| !pending.iter().any(|&(_, src)| src == Value::Variable(dst)));
+
+        if let Some(i) = ready {
+            let (dst, src) = pending.remove(i);
+            sequence.push(Instruction::Assign { dst, src });
+        } else {
+            let (dst, _) = pending[0];
+            let base = interner.resolve(dst.0).clone();
+            let temp = fresh_symbol(interner, &base);
+            sequence.push(Instruction::Assign {
+                dst: temp,
+                src: Value::Variable(dst),
+            });
+
+            for (_, src) in &mut pending {
+                if *src == Value::Variable(dst) {
+                    *src = Value::Variable(temp);
+                }
+            }
+        }
+    }
+
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::testing::{count, parse, transform};
+
+    const LOOP: &str = r"
+define @main() {
+    %n <- call input()
+    %i <- 0
+    %s <- 1
+    :loop
+    %c <- %i < %n
+    br %c :body
+    %s <- %s << 1
+    %s <- %s + 1
+    call print(%s)
+    return
+    :body
+    %i <- %i + 2
+    %s <- %s + %i
+    br :loop
+}
+";
+
+    fn num_phis(block: &BasicBlock) -> usize {
+        block
+            .instructions
+            .iter()
+            .filter(|inst| matches!(inst, Instruction::Phi { .. }))
+            .count()
+    }
+
+    fn through_ssa(func: &mut Function, interner: &mut Interner<String>) {
+        construct_ssa(func, interner);
+        destruct_ssa(func, interner);
+    }
+
+    #[test]
+    fn places_phis_for_variables_redefined_in_loops() {
+        let prog = transform(LOOP, &[&[7], &[0]], construct_ssa);
+        let blocks = &prog.functions[0].basic_blocks;
+
+        // %i and %s, but not %n which is only defined before the loop
+        assert_eq!(num_phis(&blocks[1]), 2, "\n{}", prog);
+        for block in blocks.iter().filter(|block| block.id != BlockId(1)) {
+            assert_eq!(num_phis(block), 0, "\n{}", prog);
+        }
+    }
+
+    #[test]
+    fn defines_every_variable_once() {
+        let prog = transform(LOOP, &[&[7]], construct_ssa);
+        let func = &prog.functions[0];
+
+        let mut defined: HashSet<SymbolId> = func.params.iter().copied().collect();
+        for inst in func
+            .basic_blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+        {
+            if let Some(def) = inst.defs() {
+                assert!(defined.insert(def), "\n{}", prog);
+            }
+        }
+    }
+
+    #[test]
+    fn skips_phis_for_dead_variables() {
+        let prog = transform(
+            r"
+define @main() {
+    %c <- call input()
+    %x <- 1
+    br %c :join
+    %x <- 3
+    :join
+    call print(%c)
+    return
+}
+",
+            &[&[0]],
+            construct_ssa,
+        );
+        let is_phi = |inst: &Instruction| matches!(inst, Instruction::Phi { .. });
+        assert_eq!(count(&prog, is_phi), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn removes_phis_when_leaving_ssa() {
+        let prog = transform(LOOP, &[&[7], &[0]], through_ssa);
+        let is_phi = |inst: &Instruction| matches!(inst, Instruction::Phi { .. });
+        assert_eq!(count(&prog, is_phi), 0, "\n{}", prog);
+    }
+
+    #[test]
+    fn copies_on_critical_edges_go_through_a_new_block() {
+        let prog = transform(
+            r"
+define @main() {
+    %n <- call input()
+    %i <- 1
+    :loop
+    %i <- %i + 2
+    %c <- %i < %n
+    br %c :loop
+    call print(%i)
+    return
+}
+",
+            &[&[9], &[0]],
+            through_ssa,
+        );
+        let blocks = &prog.functions[0].basic_blocks;
+
+        let target = blocks
+            .iter()
+            .find_map(|block| match block.instructions.last() {
+                Some(Instruction::BranchCond { label, .. }) => Some(*label),
+                _ => None,
+            })
+            .unwrap();
+        let edge = blocks
+            .iter()
+            .find(|block| block.instructions.first() == Some(&Instruction::Label(target)))
+            .unwrap();
+
+        assert!(
+            matches!(
+                edge.instructions.as_slice(),
+                [
+                    Instruction::Label(_),
+                    Instruction::Assign { .. },
+                    Instruction::Branch(_)
+                ]
+            ),
+            "\n{}",
+            prog
+        );
+    }
+
+    #[test]
+    fn merges_parameters_in_loops_around_the_entry() {
+        transform(
+            r"
+define @main() {
+    %x <- call @count(9)
+    call print(%x)
+    return
+}
+
+define @count(%n) {
+    :loop
+    %n <- %n - 2
+    %c <- 1 < %n
+    br %c :loop
+    return %n
+}
+",
+            &[&[]],
+            construct_ssa,
+        );
+    }
+
+    #[test]
+    fn breaks_copy_cycles_with_a_temporary() {
+        let mut prog = parse(
+            r"
+define @main() {
+    return
+}
+",
+        );
+        let [a, b, c] =
+            ["a", "b", "c"].map(|name| SymbolId(prog.interner.intern(name.to_string())));
+        let copies = [
+            (a, Value::Variable(b)),
+            (b, Value::Variable(c)),
+            (c, Value::Variable(a)),
+        ];
+
+        let sequence = sequentialize(&copies, &mut prog.interner);
+        assert_eq!(sequence.len(), 4);
+
+        let mut env: HashMap<SymbolId, i64> = HashMap::from([(a, 1), (b, 2), (c, 3)]);
+        for inst in sequence {
+            let Instruction::Assign {
+                dst,
+                src: Value::Variable(src),
+            } = inst
+            else {
+                panic!("sequence should only copy variables");
+            };
+            env.insert(dst, env[&src]);
+        }
+        assert_eq!([env[&a], env[&b], env[&c]], [2, 3, 1]);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::Interner;
+
+use crate::optimization::{evaluate_binary, evaluate_compare};
+use crate::parser::parse_source;
+
+const MAX_STEPS: usize = 1_000_000;
+const FUNCTION_BASE: i64 = 1 << 48;
+const LABEL_BASE: i64 = 1 << 50;
+
+pub fn parse(source: &str) -> Program {
+    parse_source("test.L3", source.trim_start()).expect("test program should parse")
+}
+
+// Parses `source`, runs `pass` over every function and checks that the
+// program prints the same lines for each of `inputs` before and after.
+// Returns the transformed program.
+pub fn transform(
+    source: &str,
+    inputs: &[&[i64]],
+    mut pass: impl FnMut(&mut Function, &mut Interner<String>),
+) -> Program {
+    transform_program(source, inputs, |prog| {
+        for func in &mut prog.functions {
+            pass(func, &mut prog.interner);
+        }
+    })
+}
+
+// Like `transform`, for passes over the whole program.
+pub fn transform_program(
+    source: &str,
+    inputs: &[&[i64]],
+    transform: impl FnOnce(&mut Program),
+) -> Program {
+    let mut prog = parse(source);
+    let expected: Vec<Vec<String>> = inputs
+        .iter()
+        .map(|input| run(&prog, input).output)
+        .collect();
+
+    transform(&mut prog);
+
+    for (input, expected) in inputs.iter().zip(expected) {
+        let output = run(&prog, input).output;
+        assert_eq!(output, expected, "input {:?}\n{}", input, prog);
+    }
+    prog
+}
+
+pub fn symbol(prog: &Program, name: &str) -> SymbolId {
+    (0..prog.interner.len())
+        .find(|&i| prog.interner.resolve(i) == name)
+        .map(SymbolId)
+        .unwrap_or_else(|| panic!("{} should be interned", name))
+}
+
+// The only instruction defining the variable called `name`.
+pub fn def<'a>(prog: &'a Program, name: &str) -> &'a Instruction {
+    let var = symbol(prog, name);
+    let mut defs = instructions(prog).filter(|inst| inst.defs() == Some(var));
+    match (defs.next(), defs.next()) {
+        (Some(def), None) => def,
+        _ => panic!("%{} should be defined exactly once\n{}", name, prog),
+    }
+}
+
+// How many instructions of `prog` satisfy `pred`.
+pub fn count(prog: &Program, pred: impl Fn(&Instruction) -> bool) -> usize {
+    instructions(prog).filter(|inst| pred(inst)).count()
+}
+
+fn instructions(prog: &Program) -> impl Iterator<Item = &Instruction> {
+    prog.functions
+        .iter()
+        .flat_map(|func| &func.basic_blocks)
+        .flat_map(|block| &block.instructions)
+}
+
+pub struct Execution {
+    pub output: Vec<String>,
+    pub trace: Vec<Instruction>,
+}
+
+impl Execution {
+    // How many executed instructions satisfy `pred`.
+    pub fn count(&self, pred: impl Fn(&Instruction) -> bool) -> usize {
+        self.trace.iter().filter(|inst| pred(inst)).count()
+    }
+}
+
+// Interprets `@main` and records the lines it prints, ending with "error" if
+// the program reports a runtime error, and every instruction it executes.
+pub fn run(prog: &Program, input: &[i64]) -> Execution {
+    let mut machine = Machine {
+        prog,
+        memory: vec![0],
+        input: input.iter().copied(),
+        output: Vec::new(),
+        trace: Vec::new(),
+    };
+
+    let main = prog
+        .functions
+        .iter()
+        .find(|func| prog.interner.resolve(func.name.0) == "main")
+        .expect("program should define @main");
+    if machine.call(main.name, Vec::new()).is_err() {
+        machine.output.push("error".to_string());
+    }
+
+    Execution {
+        output: machine.output,
+        trace: machine.trace,
+    }
+}
+
+struct Halt;
+
+struct Machine<'a, I> {
+    prog: &'a Program,
+    memory: Vec<i64>,
+    input: I,
+    output: Vec<String>,
+    trace: Vec<Instruction>,
+}
+
+impl<I: Iterator<Item = i64>> Machine<'_, I> {
+    fn call(&mut self, name: SymbolId, args: Vec<i64>) -> Result<i64, Halt> {
+        let func = self
+            .prog
+            .functions
+            .iter()
+            .find(|func| func.name == name)
+            .expect("callee should be defined");
+        assert_eq!(func.params.len(), args.len(), "argument count mismatch");
+
+        let blocks: HashMap<SymbolId, usize> = func
+            .basic_blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, block)| match block.instructions.first() {
+                Some(Instruction::Label(label)) => Some((*label, i)),
+                _ => None,
+            })
+            .collect();
+
+        let mut env: HashMap<SymbolId, i64> = func.params.iter().copied().zip(args).collect();
+        let mut current = 0;
+        let mut prev = None;
+
+        loop {
+            let block = &func.basic_blocks[current];
+            let mut next = current + 1;
+
+            // phis read their arguments before any of them is written
+            let phis: Vec<(SymbolId, i64)> = block
+                .instructions
+                .iter()
+                .filter_map(|inst| match inst {
+                    Instruction::Phi { dst, args } => {
+                        let (_, arg) = args
+                            .iter()
+                            .find(|(pred, _)| Some(*pred) == prev)
+                            .expect("phi should have an argument for the predecessor");
+                        Some((*dst, self.value(&env, arg)))
+                    }
+                    _ => None,
+                })
+                .collect();
+            env.extend(phis);
+
+            for inst in &block.instructions {
+                assert!(self.trace.len() < MAX_STEPS, "program does not terminate");
+                self.trace.push(inst.clone());
+
+                match inst {
+                    Instruction::Assign { dst, src } => {
+                        env.insert(*dst, self.value(&env, src));
+                    }
+                    Instruction::Binary { dst, lhs, op, rhs } => {
+                        let val = evaluate_binary(self.value(&env, lhs), op, self.value(&env, rhs));
+                        env.insert(*dst, val);
+                    }
+                    Instruction::Compare { dst, lhs, cmp, rhs } => {
+                        let val =
+                            evaluate_compare(self.value(&env, lhs), cmp, self.value(&env, rhs));
+                        env.insert(*dst, val as i64);
+                    }
+                    Instruction::Load { dst, src } => {
+                        let addr = self.variable(&env, *src);
+                        env.insert(*dst, self.memory[self.word(addr)]);
+                    }
+                    Instruction::Store { dst, src } => {
+                        let addr = self.variable(&env, *dst);
+                        let word = self.word(addr);
+                        self.memory[word] = self.value(&env, src);
+                    }
+                    Instruction::Return => return Ok(0),
+                    Instruction::ReturnValue(val) => return Ok(self.value(&env, val)),
+                    Instruction::Label(_) | Instruction::Phi { .. } => (),
+                    Instruction::Branch(label) => next = blocks[label],
+                    Instruction::BranchCond { cond, label } => {
+                        if self.value(&env, cond) == 1 {
+                            next = blocks[label];
+                        }
+                    }
+                    Instruction::Call { callee, args } => {
+                        self.call_value(&env, callee, args)?;
+                    }
+                    Instruction::CallResult { dst, callee, args } => {
+                        let val = self.call_value(&env, callee, args)?;
+                        env.insert(*dst, val);
+                    }
+                    Instruction::TailCall { callee, args } => {
+                        return self.call_value(&env, callee, args);
+                    }
+                }
+            }
+
+            assert!(
+                next < func.basic_blocks.len(),
+                "control falls off the end of the function"
+            );
+            prev = Some(BlockId(current));
+            current = next;
+        }
+    }
+
+    fn call_value(
+        &mut self,
+        env: &HashMap<SymbolId, i64>,
+        callee: &Callee,
+        args: &[Value],
+    ) -> Result<i64, Halt> {
+        let args: Vec<i64> = args.iter().map(|arg| self.value(env, arg)).collect();
+
+        match callee {
+            Callee::Value(val) => {
+                let target = self.value(env, val) - FUNCTION_BASE;
+                assert!(target >= 0, "call through a non-function value");
+                self.call(SymbolId(target as usize), args)
+            }
+            Callee::Print => {
+                let line = self.format(args[0], 0);
+                self.output.push(line);
+                Ok(1)
+            }
+            Callee::Input => Ok(self.input.next().expect("program reads past its input") * 2 + 1),
+            Callee::Allocate => {
+                let size = args[0] >> 1;
+                let addr = self.memory.len() as i64 * 8;
+                self.memory.push(size);
+                self.memory
+                    .extend(std::iter::repeat_n(args[1], size.max(1) as usize));
+                Ok(addr)
+            }
+            Callee::TupleError | Callee::TensorError => Err(Halt),
+        }
+    }
+
+    fn format(&self, val: i64, depth: usize) -> String {
+        if depth >= 4 {
+            return "...".to_string();
+        }
+        if val & 1 == 1 {
+            return (val >> 1).to_string();
+        }
+
+        let word = self.word(val);
+        let size = self.memory[word];
+        let mut line = format!("{{s:{}", size);
+        for i in 1..=size as usize {
+            line += &format!(", {}", self.format(self.memory[word + i], depth + 1));
+        }
+        line + "}"
+    }
+
+    fn word(&self, addr: i64) -> usize {
+        assert!(
+            addr > 0 && addr % 8 == 0 && (addr / 8) < self.memory.len() as i64,
+            "invalid address {}",
+            addr
+        );
+        (addr / 8) as usize
+    }
+
+    fn value(&self, env: &HashMap<SymbolId, i64>, val: &Value) -> i64 {
+        match val {
+            Value::Number(num) => *num,
+            Value::Variable(var) => self.variable(env, *var),
+            Value::Function(name) => FUNCTION_BASE + name.0 as i64,
+            Value::Label(label) => LABEL_BASE + label.0 as i64 * 8,
+        }
+    }
+
+    fn variable(&self, env: &HashMap<SymbolId, i64>, var: SymbolId) -> i64 {
+        *env.get(&var).unwrap_or_else(|| {
+            panic!(
+                "%{} is read before it is defined",
+                self.prog.interner.resolve(var.0)
+            )
+        })
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use l3::*;
+use utils::Interner;
+
+use crate::analysis::{build_def_use, compute_liveness, compute_reaching_def};
+use crate::globalization::unique_prefix;
+use crate::isel::{create_contexts, generate_forests, optimal_match};
+
+pub const ARG_REGISTERS: [l2::Register; 6] = [
+    l2::Register::RDI,
+    l2::Register::RSI,
+    l2::Register::RDX,
+    l2::Register::RCX,
+    l2::Register::R8,
+    l2::Register::R9,
+];
+
+pub fn translate_program(prog: &Program, verbose: bool) -> l2::Program {
+    let mut interner = prog.interner.clone();
+    let prefix = unique_prefix(&interner);
+    let mut suffix = 0;
+
+    let functions = prog
+        .functions
+        .iter()
+        .map(|func| translate_function(func, &prefix, &mut suffix, &mut interner, verbose))
+        .collect();
+
+    l2::Program {
+        entry_point: "main".to_string(),
+        functions,
+        interner,
+    }
+}
+
+pub fn translate_value(val: &Value) -> l2::Value {
+    match val {
+        Value::Number(num) => l2::Value::Number(*num),
+        Value::Label(label) => l2::Value::Label(l2::SymbolId(label.0)),
+        Value::Function(callee) => l2::Value::Function(l2::SymbolId(callee.0)),
+        Value::Variable(var) => l2::Value::Variable(l2::SymbolId(var.0)),
+    }
+}
+
+fn translate_function(
+    func: &Function,
+    prefix: &str,
+    suffix: &mut i32,
+    interner: &mut Interner<String>,
+    verbose: bool,
+) -> l2::Function {
+    let liveness = compute_liveness(func);
+    let reaching_def = compute_reaching_def(func);
+    let def_use = build_def_use(func, &reaching_def);
+    let mut contexts = create_contexts(func);
+    let forests = generate_forests(func, &liveness, &def_use, &mut contexts);
+
+    let mut selected: HashMap<(BlockId, usize), Vec<l2::Instruction>> = contexts
+        .iter()
+        .zip(&forests)
+        .flat_map(|(ctx, forest)| {
+            let roots = ctx.inst_ids.iter().map(|&i| (ctx.block_id, i));
+            roots.zip(optimal_match(forest, interner, verbose))
+        })
+        .collect();
+    let mut instructions = bind_params(&func.params);
+
+    for block in &func.basic_blocks {
+        for (i, inst) in block.instructions.iter().enumerate() {
+            match inst {
+                Instruction::Label(label) => {
+                    instructions.push(l2::Instruction::Label(l2::SymbolId(label.0)))
+                }
+
+                Instruction::Call { callee, args } => {
+                    instructions.extend(lower_call(callee, args, prefix, suffix, interner))
+                }
+
+                Instruction::TailCall { callee, args } => {
+                    let Callee::Value(val) = callee else {
+                        unreachable!("tail call to a runtime function");
+                    };
+                    instructions.extend(pass_arguments(args));
+                    instructions.push(l2::Instruction::TailCall {
+                        callee: translate_value(val),
+                        args: args.len() as i64,
+                    });
+                }
+
+                Instruction::CallResult { dst, callee, args } => {
+                    instructions.extend(lower_call(callee, args, prefix, suffix, interner));
+                    instructions.push(l2::Instruction::Assign {
+                        dst: l2::Value::Variable(l2::SymbolId(dst.0)),
+                        src: l2::Value::Register(l2::Register::RAX),
+                    });
+                }
+
+                _ => {
+                    if let Some(insts) = selected.remove(&(block.id, i)) {
+                        instructions.extend(insts);
+                    }
+                }
+            }
+        }
+    }
+
+    l2::Function::new(
+        l2::SymbolId(func.name.0),
+        func.params.len() as i64,
+        instructions,
+    )
+}
+
+fn bind_params(params: &[SymbolId]) -> Vec<l2::Instruction> {
+    let num_stack_params = params.len().saturating_sub(ARG_REGISTERS.len());
+
+    params
+        .iter()
+        .zip(ARG_REGISTERS)
+        .map(|(param, reg)| l2::Instruction::Assign {
+            dst: l2::Value::Variable(l2::SymbolId(param.0)),
+            src: l2::Value::Register(reg),
+        })
+        .chain(
+            params
+                .iter()
+                .skip(ARG_REGISTERS.len())
+                .enumerate()
+                .map(|(i, param)| l2::Instruction::StackArg {
+                    dst: l2::Value::Variable(l2::SymbolId(param.0)),
+                    offset: 8 * (num_stack_params - 1 - i) as i64,
+                }),
+        )
+        .collect()
+}
+
+fn pass_arguments(args: &[Value]) -> Vec<l2::Instruction> {
+    use l2::Instruction as L2;
+
+    let mut insts: Vec<l2::Instruction> = args
+        .iter()
+        .zip(ARG_REGISTERS)
+        .map(|(arg, reg)| L2::Assign {
+            dst: l2::Value::Register(reg),
+            src: translate_value(arg),
+        })
+        .collect();
+
+    insts.extend(
+        args.iter()
+            .skip(ARG_REGISTERS.len())
+            .enumerate()
+            .map(|(i, arg)| L2::Store {
+                dst: l2::Value::Register(l2::Register::RSP),
+                offset: -16 - 8 * i as i64,
+                src: translate_value(arg),
+            }),
+    );
+
+    insts
+}
+
+fn lower_call(
+    callee: &Callee,
+    args: &[Value],
+    prefix: &str,
+    suffix: &mut i32,
+    interner: &mut Interner<String>,
+) -> Vec<l2::Instruction> {
+    use l2::Instruction as L2;
+
+    let mut insts = pass_arguments(args);
+
+    match callee {
+        Callee::Value(val) => {
+            let return_label = l2::SymbolId(interner.intern(format!("{}{}", prefix, suffix)));
+            *suffix += 1;
+            insts.insert(
+                0,
+                L2::Store {
+                    dst: l2::Value::Register(l2::Register::RSP),
+                    offset: -8,
+                    src: l2::Value::Label(return_label),
+                },
+            );
+            insts.push(L2::Call {
+                callee: translate_value(val),
+                args: args.len() as i64,
+            });
+            insts.push(L2::Label(return_label));
+        }
+        Callee::Print => insts.push(L2::Print),
+        Callee::Allocate => insts.push(L2::Allocate),
+        Callee::Input => insts.push(L2::Input),
+        Callee::TupleError => insts.push(L2::TupleError),
+        Callee::TensorError => insts.push(L2::TensorError(args.len() as u8)),
+    }
+
+    insts
+}
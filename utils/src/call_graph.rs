@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bitvector::BitVector;
+use crate::interner::{DisplayResolved, Interner};
+
+// Functions are identified by their position in the program and named by
+// their interned symbol, so the same graph serves every language level.
+#[derive(Debug)]
+pub struct CallGraph {
+    names: Vec<usize>,
+    indices: HashMap<usize, usize>,
+    callees: Vec<Vec<usize>>,
+    references: Vec<Vec<usize>>,
+    indirect: BitVector,
+}
+
+impl CallGraph {
+    pub fn new(names: Vec<usize>) -> Self {
+        let num_funcs = names.len();
+        let indices = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| (name, i))
+            .collect();
+
+        Self {
+            names,
+            indices,
+            callees: vec![Vec::new(); num_funcs],
+            references: vec![Vec::new(); num_funcs],
+            indirect: BitVector::new(num_funcs),
+        }
+    }
+
+    pub fn add_call(&mut self, caller: usize, name: usize) {
+        if let Some(callee) = self.index_of(name)
+            && !self.callees[caller].contains(&callee)
+        {
+            self.callees[caller].push(callee);
+        }
+    }
+
+    pub fn add_reference(&mut self, func: usize, name: usize) {
+        if let Some(target) = self.index_of(name)
+            && !self.references[func].contains(&target)
+        {
+            self.references[func].push(target);
+        }
+    }
+
+    pub fn add_indirect_call(&mut self, caller: usize) {
+        self.indirect.set(caller);
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn index_of(&self, name: usize) -> Option<usize> {
+        self.indices.get(&name).copied()
+    }
+
+    pub fn callees(&self, func: usize) -> &[usize] {
+        &self.callees[func]
+    }
+
+    pub fn references(&self, func: usize) -> &[usize] {
+        &self.references[func]
+    }
+
+    pub fn has_indirect_calls(&self, func: usize) -> bool {
+        self.indirect.test(func)
+    }
+
+    // a function whose address escapes is kept alive by whoever holds it
+    pub fn reachable_from(&self, entry: usize) -> BitVector {
+        let mut reachable = BitVector::new(self.len());
+        let mut stack = vec![entry];
+
+        while let Some(func) = stack.pop() {
+            if !reachable.test(func) {
+                reachable.set(func);
+                stack.extend(&self.callees[func]);
+                stack.extend(&self.references[func]);
+            }
+        }
+
+        reachable
+    }
+
+    // Tarjan's algorithm, which yields every SCC after all the SCCs it calls into
+    pub fn sccs(&self) -> Vec<Vec<usize>> {
+        struct Tarjan<'a> {
+            callees: &'a [Vec<usize>],
+            index: Vec<Option<usize>>,
+            lowlink: Vec<usize>,
+            on_stack: BitVector,
+            stack: Vec<usize>,
+            counter: usize,
+            sccs: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, func: usize) {
+                self.index[func] = Some(self.counter);
+                self.lowlink[func] = self.counter;
+                self.counter += 1;
+                self.stack.push(func);
+                self.on_stack.set(func);
+
+                for &callee in &self.callees[func] {
+                    match self.index[callee] {
+                        None => {
+                            self.visit(callee);
+                            self.lowlink[func] = self.lowlink[func].min(self.lowlink[callee]);
+                        }
+                        Some(index) if self.on_stack.test(callee) => {
+                            self.lowlink[func] = self.lowlink[func].min(index);
+                        }
+                        _ => (),
+                    }
+                }
+
+                if Some(self.lowlink[func]) == self.index[func] {
+                    let mut scc = Vec::new();
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack.reset(member);
+                        scc.push(member);
+                        if member == func {
+                            break;
+                        }
+                    }
+                    self.sccs.push(scc);
+                }
+            }
+        }
+
+        let num_funcs = self.len();
+        let mut tarjan = Tarjan {
+            callees: &self.callees,
+            index: vec![None; num_funcs],
+            lowlink: vec![0; num_funcs],
+            on_stack: BitVector::new(num_funcs),
+            stack: Vec::new(),
+            counter: 0,
+            sccs: Vec::new(),
+        };
+
+        for func in 0..num_funcs {
+            if tarjan.index[func].is_none() {
+                tarjan.visit(func);
+            }
+        }
+
+        tarjan.sccs
+    }
+}
+
+impl DisplayResolved for CallGraph {
+    fn fmt_with(&self, f: &mut fmt::Formatter, interner: &Interner<String>) -> fmt::Result {
+        let name = |func: usize| interner.resolve(self.names[func]);
+
+        writeln!(f, "digraph calls {{")?;
+
+        for func in 0..self.len() {
+            if self.has_indirect_calls(func) {
+                writeln!(f, "\t\"{}\" [style=dashed];", name(func))?;
+            } else {
+                writeln!(f, "\t\"{}\";", name(func))?;
+            }
+        }
+
+        for func in 0..self.len() {
+            for &callee in self.callees(func) {
+                writeln!(f, "\t\"{}\" -> \"{}\";", name(func), name(callee))?;
+            }
+            for &target in self.references(func) {
+                writeln!(
+                    f,
+                    "\t\"{}\" -> \"{}\" [style=dotted];",
+                    name(func),
+                    name(target)
+                )?;
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::bitvector::BitVector;
+
+pub trait BlockIndex: Copy + Eq + Hash + Debug {
+    fn new(index: usize) -> Self;
+    fn index(self) -> usize;
+}
+
+pub trait ControlFlow {
+    type Block: BlockIndex;
+
+    fn num_blocks(&self) -> usize;
+    fn successors(&self, block: Self::Block) -> &[Self::Block];
+    fn predecessors(&self, block: Self::Block) -> &[Self::Block];
+}
+
+// Blocks reachable from the entry in reverse postorder, followed by the
+// unreachable ones in index order.
+pub fn reverse_postorder<G: ControlFlow>(cfg: &G) -> Vec<G::Block> {
+    let num_blocks = cfg.num_blocks();
+    let mut visited = BitVector::new(num_blocks);
+    let mut postorder = Vec::with_capacity(num_blocks);
+
+    if num_blocks > 0 {
+        let entry = G::Block::new(0);
+        let mut stack = vec![(entry, 0)];
+        visited.set(0);
+
+        while let Some((block, next)) = stack.last_mut() {
+            let block = *block;
+            if let Some(&succ) = cfg.successors(block).get(*next) {
+                *next += 1;
+                if !visited.test(succ.index()) {
+                    visited.set(succ.index());
+                    stack.push((succ, 0));
+                }
+            } else {
+                postorder.push(block);
+                stack.pop();
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder.extend(
+        (0..num_blocks)
+            .filter(|&i| !visited.test(i))
+            .map(G::Block::new),
+    );
+    postorder
+}
@@ -0,0 +1,220 @@
+use std::collections::BTreeSet;
+
+use crate::bitvector::BitVector;
+use crate::cfg::{BlockIndex, ControlFlow, reverse_postorder};
+
+pub trait Dataflow {
+    type Block: BlockIndex;
+
+    const DIRECTION: Direction;
+
+    // the set flowing into the entry for forward problems, and into every
+    // exit for backward ones
+    fn boundary(&self) -> BitVector;
+
+    // the initial guess everywhere else, which must be the identity of `meet`
+    fn top(&self) -> BitVector {
+        self.boundary()
+    }
+
+    fn meet(&self, current: &mut BitVector, other: &BitVector);
+
+    fn transfer(&self, input: &BitVector, block: Self::Block) -> BitVector;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+// Returns the sets on entry to and exit from every block, in the direction of
+// the analysis. Blocks are visited in reverse postorder for forward problems
+// and in postorder for backward ones, so most of them see their inputs
+// settled before they are first transferred.
+pub fn solve<G, D>(cfg: &G, dataflow: &D) -> (Vec<BitVector>, Vec<BitVector>)
+where
+    G: ControlFlow,
+    D: Dataflow<Block = G::Block>,
+{
+    let num_blocks = cfg.num_blocks();
+    let mut block_enter = vec![dataflow.top(); num_blocks];
+    let mut block_exit = vec![dataflow.top(); num_blocks];
+
+    let mut order = reverse_postorder(cfg);
+    if D::DIRECTION == Direction::Backward {
+        order.reverse();
+    }
+
+    let mut position = vec![0; num_blocks];
+    for (i, block) in order.iter().enumerate() {
+        position[block.index()] = i;
+    }
+
+    let mut worklist: BTreeSet<usize> = (0..num_blocks).collect();
+
+    while let Some(next) = worklist.pop_first() {
+        let block = order[next];
+        let i = block.index();
+
+        let is_boundary = match D::DIRECTION {
+            Direction::Forward => i == 0,
+            Direction::Backward => cfg.successors(block).is_empty(),
+        };
+        block_enter[i] = if is_boundary {
+            dataflow.boundary()
+        } else {
+            dataflow.top()
+        };
+
+        let enter_neighbors = match D::DIRECTION {
+            Direction::Forward => cfg.predecessors(block),
+            Direction::Backward => cfg.successors(block),
+        };
+        for neighbor in enter_neighbors {
+            dataflow.meet(&mut block_enter[i], &block_exit[neighbor.index()]);
+        }
+
+        let temp = dataflow.transfer(&block_enter[i], block);
+
+        if temp != block_exit[i] {
+            block_exit[i] = temp;
+
+            let exit_neighbors = match D::DIRECTION {
+                Direction::Forward => cfg.successors(block),
+                Direction::Backward => cfg.predecessors(block),
+            };
+            worklist.extend(
+                exit_neighbors
+                    .iter()
+                    .map(|neighbor| position[neighbor.index()]),
+            );
+        }
+    }
+
+    (block_enter, block_exit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Block(usize);
+
+    impl BlockIndex for Block {
+        fn new(index: usize) -> Self {
+            Block(index)
+        }
+
+        fn index(self) -> usize {
+            self.0
+        }
+    }
+
+    struct Graph {
+        successors: Vec<Vec<Block>>,
+        predecessors: Vec<Vec<Block>>,
+    }
+
+    impl Graph {
+        fn new(num_blocks: usize, edges: &[(usize, usize)]) -> Self {
+            let mut successors = vec![Vec::new(); num_blocks];
+            let mut predecessors = vec![Vec::new(); num_blocks];
+            for &(from, to) in edges {
+                successors[from].push(Block(to));
+                predecessors[to].push(Block(from));
+            }
+            Graph {
+                successors,
+                predecessors,
+            }
+        }
+    }
+
+    impl ControlFlow for Graph {
+        type Block = Block;
+
+        fn num_blocks(&self) -> usize {
+            self.successors.len()
+        }
+
+        fn successors(&self, block: Block) -> &[Block] {
+            &self.successors[block.0]
+        }
+
+        fn predecessors(&self, block: Block) -> &[Block] {
+            &self.predecessors[block.0]
+        }
+    }
+
+    // Every block adds itself to the set. Forward with intersection this
+    // yields the blocks on every path from the entry, backward with union the
+    // blocks on some path to an exit.
+    struct Visited<const FORWARD: bool>(usize);
+
+    impl<const FORWARD: bool> Dataflow for Visited<FORWARD> {
+        type Block = Block;
+
+        const DIRECTION: Direction = if FORWARD {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+
+        fn boundary(&self) -> BitVector {
+            BitVector::new(self.0)
+        }
+
+        fn top(&self) -> BitVector {
+            let mut top = BitVector::new(self.0);
+            if FORWARD {
+                top.set_from(0..self.0);
+            }
+            top
+        }
+
+        fn meet(&self, current: &mut BitVector, other: &BitVector) {
+            if FORWARD {
+                current.intersection(other);
+            } else {
+                current.union(other);
+            }
+        }
+
+        fn transfer(&self, input: &BitVector, block: Block) -> BitVector {
+            let mut output = input.clone();
+            output.set(block.0);
+            output
+        }
+    }
+
+    // a diamond whose join loops back into one of its arms
+    fn graph() -> Graph {
+        Graph::new(5, &[(0, 1), (0, 2), (1, 3), (2, 3), (3, 1), (3, 4)])
+    }
+
+    fn blocks(set: &BitVector) -> Vec<usize> {
+        set.iter().collect()
+    }
+
+    #[test]
+    fn solves_forward_problems_through_loops() {
+        let (enter, exit) = solve(&graph(), &Visited::<true>(5));
+        assert!(!enter[0].any());
+        assert_eq!(blocks(&enter[1]), [0]);
+        assert_eq!(blocks(&enter[3]), [0]);
+        assert_eq!(blocks(&exit[3]), [0, 3]);
+        assert_eq!(blocks(&enter[4]), [0, 3]);
+    }
+
+    #[test]
+    fn solves_backward_problems_through_loops() {
+        let (enter, exit) = solve(&graph(), &Visited::<false>(5));
+        assert!(!enter[4].any());
+        assert_eq!(blocks(&enter[3]), [1, 3, 4]);
+        assert_eq!(blocks(&enter[2]), [1, 3, 4]);
+        assert_eq!(blocks(&enter[0]), [1, 2, 3, 4]);
+        assert_eq!(blocks(&exit[0]), [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::bitvector::BitVector;
+use crate::cfg::{BlockIndex, ControlFlow};
+use crate::worklist::Worklist;
+
+#[derive(Debug)]
+pub struct DominatorTree<B> {
+    idom: Vec<Option<B>>,
+    children: Vec<Vec<B>>,
+    frontiers: Vec<Vec<B>>,
+    reachable: BitVector,
+    preorder: Vec<u32>,
+    postorder: Vec<u32>,
+}
+
+impl<B: BlockIndex> DominatorTree<B> {
+    pub fn new<G: ControlFlow<Block = B>>(cfg: &G) -> Self {
+        let num_blocks = cfg.num_blocks();
+        let edges = |f: fn(&G, B) -> &[B]| -> Vec<Vec<usize>> {
+            (0..num_blocks)
+                .map(|i| f(cfg, B::new(i)).iter().map(|b| b.index()).collect())
+                .collect()
+        };
+
+        Self::build(0, &edges(G::successors), &edges(G::predecessors))
+    }
+
+    pub fn post_dominators<G: ControlFlow<Block = B>>(cfg: &G) -> Self {
+        let num_blocks = cfg.num_blocks();
+        let exit = num_blocks;
+        let exits: Vec<usize> = (0..num_blocks)
+            .filter(|&i| cfg.successors(B::new(i)).is_empty())
+            .collect();
+
+        let mut successors: Vec<Vec<usize>> = (0..num_blocks)
+            .map(|i| {
+                cfg.predecessors(B::new(i))
+                    .iter()
+                    .map(|b| b.index())
+                    .collect()
+            })
+            .collect();
+        successors.push(exits.clone());
+
+        let mut predecessors: Vec<Vec<usize>> = (0..num_blocks)
+            .map(|i| {
+                let succs = cfg.successors(B::new(i)).iter().map(|b| b.index());
+                succs.chain(exits.contains(&i).then_some(exit)).collect()
+            })
+            .collect();
+        predecessors.push(Vec::new());
+
+        let mut tree = Self::build(exit, &successors, &predecessors);
+
+        for idom in &mut tree.idom {
+            if idom.is_some_and(|b| b.index() == exit) {
+                *idom = None;
+            }
+        }
+
+        tree.idom.truncate(num_blocks);
+        tree.children.truncate(num_blocks);
+        tree.frontiers.truncate(num_blocks);
+        tree.preorder.truncate(num_blocks);
+        tree.postorder.truncate(num_blocks);
+
+        tree
+    }
+
+    fn build(entry: usize, successors: &[Vec<usize>], predecessors: &[Vec<usize>]) -> Self {
+        let num_nodes = successors.len();
+
+        let mut sdom = vec![BitVector::new(num_nodes); num_nodes];
+        for dom in &mut sdom {
+            dom.set_from(0..num_nodes);
+        }
+
+        let mut reachable = BitVector::new(num_nodes);
+        let mut worklist = Worklist::new();
+        worklist.push(entry);
+
+        while let Some(i) = worklist.pop() {
+            let mut temp = BitVector::new(num_nodes);
+
+            if i != entry {
+                temp.set_from(0..num_nodes);
+                for &pred in &predecessors[i] {
+                    temp.intersection(&sdom[pred]);
+                }
+            }
+
+            temp.set(i);
+
+            if temp != sdom[i] || !reachable.test(i) {
+                reachable.set(i);
+                sdom[i] = temp;
+                worklist.extend(successors[i].iter().copied());
+            }
+        }
+
+        for (i, dom) in sdom.iter_mut().enumerate() {
+            dom.reset(i);
+        }
+
+        let idom: Vec<Option<usize>> = sdom
+            .iter()
+            .enumerate()
+            .map(|(i, dom)| {
+                if reachable.test(i) {
+                    dom.iter().max_by_key(|&n| sdom[n].count())
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut children = vec![Vec::new(); num_nodes];
+        for (node, &parent) in idom.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[parent].push(B::new(node));
+            }
+        }
+
+        let mut frontiers = vec![Vec::new(); num_nodes];
+        for (i, preds) in predecessors.iter().enumerate() {
+            if preds.len() < 2 || !reachable.test(i) {
+                continue;
+            }
+
+            for &pred in preds {
+                let mut runner = Some(pred);
+                while let Some(node) = runner
+                    && reachable.test(node)
+                    && runner != idom[i]
+                {
+                    if !frontiers[node].contains(&B::new(i)) {
+                        frontiers[node].push(B::new(i));
+                    }
+                    runner = idom[node];
+                }
+            }
+        }
+
+        let mut counter = 0;
+        let mut preorder = vec![0; num_nodes];
+        let mut postorder = vec![0; num_nodes];
+
+        fn dfs<B: BlockIndex>(
+            node: usize,
+            children: &[Vec<B>],
+            counter: &mut u32,
+            preorder: &mut [u32],
+            postorder: &mut [u32],
+        ) {
+            preorder[node] = *counter;
+            *counter += 1;
+
+            for &child in &children[node] {
+                dfs(child.index(), children, counter, preorder, postorder);
+            }
+
+            postorder[node] = *counter;
+            *counter += 1;
+        }
+
+        dfs(
+            entry,
+            &children,
+            &mut counter,
+            &mut preorder,
+            &mut postorder,
+        );
+
+        Self {
+            idom: idom.into_iter().map(|node| node.map(B::new)).collect(),
+            children,
+            frontiers,
+            reachable,
+            preorder,
+            postorder,
+        }
+    }
+
+    pub fn idom(&self, block: B) -> Option<B> {
+        self.idom[block.index()]
+    }
+
+    pub fn children(&self, block: B) -> &[B] {
+        &self.children[block.index()]
+    }
+
+    pub fn frontier(&self, block: B) -> &[B] {
+        &self.frontiers[block.index()]
+    }
+
+    pub fn is_reachable(&self, block: B) -> bool {
+        self.reachable.test(block.index())
+    }
+
+    pub fn dominates(&self, u: B, v: B) -> bool {
+        let (u, v) = (u.index(), v.index());
+        self.reachable.test(u)
+            && self.reachable.test(v)
+            && self.preorder[u] <= self.preorder[v]
+            && self.postorder[u] >= self.postorder[v]
+    }
+}
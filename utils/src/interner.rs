@@ -32,6 +32,7 @@ impl<'a, T: DisplayResolved + ?Sized> fmt::Display for DisplayResolvedWrapper<'a
 pub struct Interner<T> {
     map: HashMap<T, usize>,
     vec: Vec<T>,
+    counters: HashMap<String, usize>,
 }
 
 impl<T: Clone + Eq + Hash> Interner<T> {
@@ -39,6 +40,7 @@ impl<T: Clone + Eq + Hash> Interner<T> {
         Self {
             map: HashMap::new(),
             vec: Vec::new(),
+            counters: HashMap::new(),
         }
     }
 
@@ -50,6 +52,10 @@ impl<T: Clone + Eq + Hash> Interner<T> {
         })
     }
 
+    pub fn get(&self, item: &T) -> Option<usize> {
+        self.map.get(item).copied()
+    }
+
     pub fn resolve(&self, index: usize) -> &T {
         &self.vec[index]
     }
@@ -57,6 +63,26 @@ impl<T: Clone + Eq + Hash> Interner<T> {
     pub fn len(&self) -> usize {
         self.vec.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+}
+
+impl Interner<String> {
+    // Interns the first `{base}_{n}` not taken yet. Each base keeps its own
+    // counter, so no candidate is ever tried twice.
+    pub fn fresh(&mut self, base: &str) -> usize {
+        let counter = self.counters.entry(base.to_string()).or_default();
+        let name = loop {
+            *counter += 1;
+            let name = format!("{}_{}", base, counter);
+            if !self.map.contains_key(&name) {
+                break name;
+            }
+        };
+        self.intern(name)
+    }
 }
 
 impl<T: Eq + Hash> Index<&T> for Interner<T> {
@@ -66,3 +92,26 @@ impl<T: Eq + Hash> Index<&T> for Interner<T> {
         &self.map[item]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresh_names_skip_taken_ones() {
+        let mut interner = Interner::new();
+        interner.intern("x_2".to_string());
+
+        let names: Vec<usize> = (0..3).map(|_| interner.fresh("x")).collect();
+        let names: Vec<&str> = names
+            .iter()
+            .map(|&i| interner.resolve(i).as_str())
+            .collect();
+        assert_eq!(names, ["x_1", "x_3", "x_4"]);
+
+        let y = interner.fresh("y");
+        assert_eq!(interner.resolve(y), "y_1");
+        let x = interner.fresh("x_1");
+        assert_eq!(interner.resolve(x), "x_1_1");
+    }
+}
@@ -1,7 +1,17 @@
 mod bitvector;
+mod call_graph;
+mod cfg;
+mod dataflow;
+mod dominators;
 mod interner;
+mod loops;
 mod worklist;
 
 pub use bitvector::BitVector;
+pub use call_graph::CallGraph;
+pub use cfg::{BlockIndex, ControlFlow, reverse_postorder};
+pub use dataflow::{Dataflow, Direction, solve};
+pub use dominators::DominatorTree;
 pub use interner::{DisplayResolved, Interner};
+pub use loops::{Loop, LoopForest, LoopId};
 pub use worklist::Worklist;
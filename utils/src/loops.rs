@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use crate::bitvector::BitVector;
+use crate::cfg::{BlockIndex, ControlFlow};
+use crate::dominators::DominatorTree;
+
+pub type LoopId = usize;
+
+#[derive(Debug)]
+pub struct LoopForest<B> {
+    merged_loops: Vec<Loop<B>>,
+    block_map: HashMap<B, LoopId>,
+}
+
+impl<B: BlockIndex> LoopForest<B> {
+    pub fn new<G: ControlFlow<Block = B>>(cfg: &G, dt: &DominatorTree<B>) -> Self {
+        let num_blocks = cfg.num_blocks();
+
+        let back_edges = (0..num_blocks).map(B::new).flat_map(|latch| {
+            cfg.successors(latch)
+                .iter()
+                .filter_map(move |&header| dt.dominates(header, latch).then_some((latch, header)))
+        });
+
+        let natural_loops = back_edges.map(|(latch, header)| {
+            let mut stack = vec![latch];
+            let mut loop_blocks = BitVector::new(num_blocks);
+            loop_blocks.set(header.index());
+
+            while let Some(id) = stack.pop() {
+                let i = id.index();
+                if !loop_blocks.test(i) {
+                    loop_blocks.set(i);
+                    stack.extend(cfg.predecessors(id).iter().copied());
+                }
+            }
+
+            (header, loop_blocks)
+        });
+
+        let mut merged_loops: Vec<Loop<B>> = natural_loops
+            .fold(
+                vec![BitVector::new(num_blocks); num_blocks],
+                |mut merged_loops, (header, blocks)| {
+                    merged_loops[header.index()].union(&blocks);
+                    merged_loops
+                },
+            )
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, blocks)| {
+                blocks.any().then_some(Loop {
+                    header: B::new(i),
+                    basic_blocks: blocks.iter().map(B::new).collect(),
+                    depth: 0,
+                    parent: None,
+                    children: Vec::new(),
+                })
+            })
+            .collect();
+        merged_loops.sort_by_key(|loop_| loop_.basic_blocks.len());
+
+        let mut roots = Vec::new();
+        let mut block_map = HashMap::new();
+
+        for i in 0..merged_loops.len() {
+            for &id in &merged_loops[i].basic_blocks {
+                block_map.entry(id).or_insert(i);
+            }
+
+            let (first, second) = merged_loops.split_at_mut(i + 1);
+            let loop_header = first[i].header;
+
+            let parent = second.iter_mut().enumerate().find(|(_, other)| {
+                dt.dominates(other.header, loop_header) && other.basic_blocks.contains(&loop_header)
+            });
+
+            match parent {
+                Some((j, parent)) => {
+                    parent.children.push(i);
+                    first[i].parent = Some(i + 1 + j);
+                }
+                None => roots.push(i),
+            }
+        }
+
+        let mut stack: Vec<(LoopId, u32)> = roots.iter().map(|&root| (root, 1)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            let loop_ = &mut merged_loops[node];
+            loop_.depth = depth;
+            for &child in &loop_.children {
+                stack.push((child, depth + 1));
+            }
+        }
+
+        Self {
+            merged_loops,
+            block_map,
+        }
+    }
+
+    pub fn loops(&self) -> &[Loop<B>] {
+        &self.merged_loops
+    }
+
+    pub fn innermost_loop(&self, block: B) -> Option<LoopId> {
+        self.block_map.get(&block).copied()
+    }
+
+    pub fn loop_depth(&self, block: B) -> u32 {
+        match self.block_map.get(&block) {
+            Some(&loop_id) => self.merged_loops[loop_id].depth,
+            None => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Loop<B> {
+    header: B,
+    basic_blocks: Vec<B>,
+    depth: u32,
+    parent: Option<LoopId>,
+    children: Vec<LoopId>,
+}
+
+impl<B: BlockIndex> Loop<B> {
+    pub fn header(&self) -> B {
+        self.header
+    }
+
+    pub fn basic_blocks(&self) -> &[B] {
+        &self.basic_blocks
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn parent(&self) -> Option<LoopId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[LoopId] {
+        &self.children
+    }
+
+    pub fn contains(&self, block: B) -> bool {
+        self.basic_blocks.contains(&block)
+    }
+}
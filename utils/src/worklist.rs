@@ -7,6 +7,12 @@ pub struct Worklist<T> {
     set: HashSet<T>,
 }
 
+impl<T: Copy + Eq + Hash> Default for Worklist<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Copy + Eq + Hash> Worklist<T> {
     pub fn new() -> Self {
         Self {
@@ -16,9 +22,8 @@ impl<T: Copy + Eq + Hash> Worklist<T> {
     }
 
     pub fn pop(&mut self) -> Option<T> {
-        self.queue.pop_front().and_then(|id| {
-            self.set.remove(&id);
-            Some(id)
+        self.queue.pop_front().inspect(|id| {
+            self.set.remove(id);
         })
     }
 